@@ -1,5 +1,6 @@
 use crate::types::{
     board_state::BoardState,
+    chess_move::Move,
     colour::Colour,
     piece_type::PieceType,
     position::Position,
@@ -9,10 +10,11 @@ use crate::types::{
 
 pub const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
+/// Parses a FEN string. The move counters may be left off, as in EPD, and default to `0 1`
 pub fn parse(notation: &str) -> Result<BoardState, EngineError> {
     let segments: Vec<String> = notation.split_whitespace().map(str::to_string).collect();
 
-    if segments.len() != 6 {
+    if !(4..=6).contains(&segments.len()) {
         return Err(EngineError(String::from(
             "[fen::parse()] Too few or too may arguments to parser",
         )));
@@ -46,14 +48,24 @@ pub fn parse(notation: &str) -> Result<BoardState, EngineError> {
         None
     };
 
-    Ok(BoardState {
+    let mut board: BoardState = BoardState {
         position,
         active_player: to_move,
         en_passant: en_passant_target,
         castling_rights,
-        half_moves: segments[4].parse().unwrap(),
-        full_moves: segments[5].parse().unwrap(),
-    })
+        half_moves: segments
+            .get(4)
+            .map_or(Ok(0), |moves: &String| moves.parse())?,
+        full_moves: segments
+            .get(5)
+            .map_or(Ok(1), |moves: &String| moves.parse())?,
+        key: 0,
+        pawn_key: 0,
+    };
+    board.key = board.compute_key();
+    board.pawn_key = board.compute_pawn_key();
+
+    Ok(board)
 }
 
 fn parse_pieces(string: &str) -> Result<Position, EngineError> {
@@ -61,6 +73,13 @@ fn parse_pieces(string: &str) -> Result<Position, EngineError> {
 
     let s = string.split('/').collect::<Vec<&str>>();
 
+    if s.len() != 8 {
+        return Err(EngineError(format!(
+            "[fen::parse_pieces()] Expected 8 ranks, found {}",
+            s.len()
+        )));
+    }
+
     for (rank, contents) in s.into_iter().enumerate() {
         let mut file: u64 = 0;
 
@@ -83,11 +102,146 @@ fn parse_pieces(string: &str) -> Result<Position, EngineError> {
                 file += 1;
             }
         }
+
+        if file != 8 {
+            return Err(EngineError(format!(
+                "[fen::parse_pieces()] Rank '{contents}' does not cover 8 files"
+            )));
+        }
     }
 
     Ok(pos)
 }
 
+/// A position from an EPD record with its operations, e.g. `bm Nf3; id "WAC.001";`
+#[derive(Debug, Clone)]
+pub struct Epd {
+    pub board: BoardState,
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+impl Epd {
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(name, _)| name == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.operation("id")
+            .and_then(|operands: &[String]| operands.first())
+            .map(String::as_str)
+    }
+
+    /// The moves of a `bm` (best move) operation
+    pub fn best_moves(&self) -> Result<Vec<Move>, EngineError> {
+        self.moves("bm")
+    }
+
+    /// The moves of an `am` (avoid move) operation
+    pub fn avoid_moves(&self) -> Result<Vec<Move>, EngineError> {
+        self.moves("am")
+    }
+
+    /// The expected perft node counts of the `D1` to `D6` operations, as (depth, nodes)
+    pub fn perft_counts(&self) -> Result<Vec<(i32, usize)>, EngineError> {
+        (1..=6)
+            .filter_map(|depth: i32| {
+                self.operation(&format!("D{depth}"))
+                    .and_then(|operands: &[String]| operands.first())
+                    .map(|nodes: &String| Ok((depth, nodes.parse::<usize>()?)))
+            })
+            .collect()
+    }
+
+    fn moves(&self, opcode: &str) -> Result<Vec<Move>, EngineError> {
+        self.operation(opcode)
+            .unwrap_or_default()
+            .iter()
+            .map(|san: &String| Move::from_san(san, &self.board))
+            .collect()
+    }
+}
+
+/// Parses an EPD record: the first four FEN fields followed by `;` terminated operations, each an
+/// opcode and its operands. The `hmvc` and `fmvn` operations set the move counters.
+pub fn parse_epd(record: &str) -> Result<Epd, EngineError> {
+    let mut rest: &str = record.trim();
+    let mut fields: Vec<&str> = Vec::new();
+
+    for _ in 0..4 {
+        let (field, remaining) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        fields.push(field);
+        rest = remaining.trim_start();
+    }
+
+    let mut board: BoardState = parse(&fields.join(" "))?;
+    let mut operations: Vec<(String, Vec<String>)> = Vec::new();
+    let mut chars = rest.chars().peekable();
+
+    while chars.peek().is_some() {
+        let mut operands: Vec<String> = Vec::new();
+        let mut word: String = String::new();
+        let mut quoted: bool = false;
+
+        for c in chars.by_ref() {
+            match c {
+                '"' => quoted = !quoted,
+                ';' if !quoted => break,
+                c if c.is_whitespace() && !quoted => {
+                    if !word.is_empty() {
+                        operands.push(std::mem::take(&mut word));
+                    }
+                    continue;
+                }
+                c => word.push(c),
+            }
+
+            // A quoted operand is kept even if empty
+            if c == '"' && !quoted {
+                operands.push(std::mem::take(&mut word));
+            }
+        }
+
+        if quoted {
+            return Err(EngineError(format!(
+                "[fen::parse_epd()] Unterminated string in '{record}'"
+            )));
+        }
+
+        if !word.is_empty() {
+            operands.push(word);
+        }
+
+        if operands.is_empty() {
+            continue;
+        }
+
+        let opcode: String = operands.remove(0);
+        operations.push((opcode, operands));
+    }
+
+    let epd_counter = |opcode: &str| -> Result<Option<i32>, EngineError> {
+        operations
+            .iter()
+            .find(|(name, _)| name == opcode)
+            .and_then(|(_, operands)| operands.first())
+            .map(|count: &String| count.parse::<i32>().map_err(Into::into))
+            .transpose()
+    };
+
+    if let Some(half_moves) = epd_counter("hmvc")? {
+        board.half_moves = half_moves;
+    }
+
+    if let Some(full_moves) = epd_counter("fmvn")? {
+        board.full_moves = full_moves;
+    }
+
+    Ok(Epd { board, operations })
+}
+
 pub fn board_to_fen(board: &BoardState) -> Result<String, EngineError> {
     let mut fen: String = "".to_string();
 
@@ -160,3 +314,39 @@ pub fn board_to_fen(board: &BoardState) -> Result<String, EngineError> {
 
     Ok(fen)
 }
+
+#[cfg(test)]
+mod test {
+    use super::{board_to_fen, parse, parse_epd, Epd, START};
+
+    #[test]
+    fn move_counters_are_optional() {
+        let board = parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -").unwrap();
+        assert_eq!(board_to_fen(&board).unwrap(), START);
+        assert!(parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq").is_err());
+    }
+
+    #[test]
+    fn parses_epd_operations() {
+        let epd: Epd = parse_epd(
+            r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001"; c0 "mate; in 3";"#,
+        )
+        .unwrap();
+
+        assert_eq!(epd.id(), Some("WAC.001"));
+        assert_eq!(epd.operation("c0").unwrap(), ["mate; in 3"]);
+        assert_eq!(epd.best_moves().unwrap()[0].to_string(), "g3g6");
+        assert!(epd.avoid_moves().unwrap().is_empty());
+
+        let epd: Epd =
+            parse_epd("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - D1 26; D2 568 ;D3 13744; hmvc 3; fmvn 20")
+                .unwrap();
+
+        assert_eq!(epd.operation("D2").unwrap(), ["568"]);
+        assert_eq!(epd.operation("D3").unwrap(), ["13744"]);
+        assert_eq!(epd.perft_counts().unwrap(), [(1, 26), (2, 568), (3, 13744)]);
+        assert_eq!((epd.board.half_moves, epd.board.full_moves), (3, 20));
+
+        assert!(parse_epd("8/8/8/8/8/8/8/8 w - - id \"open").is_err());
+    }
+}
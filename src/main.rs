@@ -3,6 +3,7 @@
 mod fen;
 mod magics;
 mod movegen;
+mod pgn;
 mod search;
 mod types;
 mod uci;
@@ -1,5 +1,3 @@
-use std::time::Instant;
-
 use crate::{
     magics,
     types::{
@@ -15,7 +13,7 @@ use crate::{
 
 use self::{lookup::Lookup, EngineError};
 
-mod lookup;
+pub mod lookup;
 
 pub struct MoveGen {
     board: BoardState,
@@ -33,6 +31,14 @@ impl Default for MoveGen {
 
 const MAX_MOVES: usize = 256;
 
+/// Which pseudo-legal moves are generated before legality filtering
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenType {
+    All,
+    /// Captures and promotions only, for the quiescence search
+    Captures,
+}
+
 impl MoveGen {
     pub fn is_check(&self, board: &BoardState, colour: Colour) -> bool {
         let king = board.position.bb(colour, King).trailing_zeros() as SquareIndex;
@@ -40,96 +46,44 @@ impl MoveGen {
         self.is_attacked(board, king)
     }
 
-    pub fn trace_generate(&mut self, board: &BoardState) -> Result<Vec<Move>, EngineError> {
-        println!("\n -- Trace Move Generation -- \n");
-
-        println!("{}", board);
-
-        // assert_eq!(
-        //     &fen::parse("rnbqkb1r/1ppppppp/p6n/8/8/3P4/PPP1PPPP/RN1QKBNR w KQkq - 1 2")?,
-        //     board
-        // );
-
-        let now: Instant = Instant::now();
-
-        let mut list: Vec<Move> = Vec::with_capacity(MAX_MOVES);
-
-        self.board = *board;
-
-        Self::gen_pseudo_legal_pawn_moves(board, &mut list);
-        Self::gen_pseudo_legal_castles(board, &mut list);
-        self.gen_pseudo_legal_moves(board, &mut list, Knight);
-
-        let mut king_moves = vec![];
-
-        self.gen_pseudo_legal_moves(board, &mut king_moves, King);
-
-        println!("King moves: {:?}", king_moves);
-
-        list.append(&mut king_moves);
-
-        self.gen_pseudo_legal_moves(board, &mut list, Rook);
-        self.gen_pseudo_legal_moves(board, &mut list, Bishop);
-        self.gen_pseudo_legal_moves(board, &mut list, Queen);
-
-        let king_square: SquareIndex = board
+    /// The pieces giving check to the side to move
+    pub fn checkers(&self, board: &BoardState) -> BB {
+        let king = board
             .position
             .bb(board.active_player, King)
             .trailing_zeros() as SquareIndex;
 
-        println!("King square: {}", king_square);
-
-        if king_square > 63 {
-            return Err(EngineError(String::from(
-                "[MoveGen::all_moves()] No king piece found",
-            )));
-        }
-
-        println!("Before legality: {}ms", now.elapsed().as_millis());
-
-        let blockers: BB = self.calculate_blockers(board, king_square);
-        let checkers: BB = self.attacks_to(board, king_square);
-
-        println!("Checkers: {}", checkers);
-        println!("Blockers: {}", blockers);
-
-        println!(
-            "After blockers and checkers: {}ms",
-            now.elapsed().as_millis()
-        );
-
-        println!("Before legal check: ");
-        for mv in &list {
-            println!("{}", mv);
-        }
-
-        list.retain(|mv: &Move| self.is_legal(board, mv, blockers, checkers, king_square));
-
-        println!("After legal check: ");
-        for mv in &list {
-            println!("{}", mv);
-        }
-
-        println!("Elapsed: {}ms", now.elapsed().as_millis());
+        self.attacks_to(board, king)
+    }
 
-        println!("\n -- End Trace Move Generation -- \n");
+    pub fn all_moves(&mut self, board: &BoardState) -> Result<Vec<Move>, EngineError> {
+        self.generate(board, GenType::All)
+    }
 
-        Ok(list)
+    /// Legal captures and promotions, without generating quiet moves at all
+    pub fn captures(&mut self, board: &BoardState) -> Result<Vec<Move>, EngineError> {
+        self.generate(board, GenType::Captures)
     }
 
-    pub fn all_moves(&mut self, board: &BoardState) -> Result<Vec<Move>, EngineError> {
+    fn generate(&mut self, board: &BoardState, gen: GenType) -> Result<Vec<Move>, EngineError> {
         let mut list: Vec<Move> = Vec::with_capacity(MAX_MOVES);
 
         self.board = *board;
 
-        Self::gen_pseudo_legal_pawn_moves(board, &mut list);
-        Self::gen_pseudo_legal_castles(board, &mut list);
-        self.gen_pseudo_legal_moves(board, &mut list, Knight);
-        self.gen_pseudo_legal_moves(board, &mut list, King);
+        match gen {
+            GenType::All => {
+                Self::gen_pseudo_legal_pawn_moves(board, &mut list);
+                Self::gen_pseudo_legal_castles(board, &mut list);
+            }
+            GenType::Captures => Self::gen_pseudo_legal_pawn_captures(board, &mut list),
+        }
+
+        self.gen_pseudo_legal_moves(board, &mut list, Knight, gen);
+        self.gen_pseudo_legal_moves(board, &mut list, King, gen);
 
-        self.gen_pseudo_legal_moves(board, &mut list, Rook);
-        self.gen_pseudo_legal_moves(board, &mut list, Bishop);
-        self.gen_pseudo_legal_moves(board, &mut list, Queen);
+        self.gen_pseudo_legal_moves(board, &mut list, Rook, gen);
+        self.gen_pseudo_legal_moves(board, &mut list, Bishop, gen);
+        self.gen_pseudo_legal_moves(board, &mut list, Queen, gen);
 
         let king_square: SquareIndex = board
             .position
@@ -152,13 +106,19 @@ impl MoveGen {
         Ok(list)
     }
 
-    fn gen_pseudo_legal_moves(&self, board: &BoardState, list: &mut Vec<Move>, piece: PieceType) {
+    fn gen_pseudo_legal_moves(
+        &self,
+        board: &BoardState,
+        list: &mut Vec<Move>,
+        piece: PieceType,
+        gen: GenType,
+    ) {
         let us: Colour = board.active_player;
         let pieces: BB = board.position.bb(us, piece);
         let opponent_pieces: BB = board.position.bb_colour(!us);
         let empty_squares: BB = !board.position.bb_all();
 
-        for (square, _) in pieces.iter() {
+        for square in pieces.iter() {
             let destinations: u64 = match piece {
                 PieceType::King | PieceType::Knight => self.lookup.moves(square, piece),
                 _ => self
@@ -169,7 +129,10 @@ impl MoveGen {
             let quiets: u64 = destinations & empty_squares;
 
             Self::extract_moves(square, captures, list, Normal);
-            Self::extract_moves(square, quiets, list, Normal);
+
+            if gen == GenType::All {
+                Self::extract_moves(square, quiets, list, Normal);
+            }
         }
     }
 
@@ -194,6 +157,15 @@ impl MoveGen {
         Self::gen_promotions(board, list, pawns, dir);
     }
 
+    fn gen_pseudo_legal_pawn_captures(board: &BoardState, list: &mut Vec<Move>) {
+        let pawns: BB = board.position.bb(board.active_player, Pawn);
+        let dir: PawnDir = PawnDir::new(board.active_player);
+
+        Self::gen_pawn_captures(board, list, pawns, dir);
+        Self::gen_en_passant(board, list, pawns, dir);
+        Self::gen_promotions(board, list, pawns, dir);
+    }
+
     fn gen_quiet_pushes(board: &BoardState, list: &mut Vec<Move>, pawns: BB, dir: PawnDir) {
         let pawns: BB = pawns & !dir.rank7;
         let empty_squares: BB = !board.position.bb_all();
@@ -523,7 +495,7 @@ impl MoveGen {
 
         let mut blockers: BB = 0;
 
-        for (i, _) in snipers.iter() {
+        for i in snipers.iter() {
             let ignore = 1 << i;
             let potential_blockers = self.lookup.ray_between(king_square, i)
                 & occupancy
@@ -598,7 +570,7 @@ pub mod test {
         movegen::MoveGen,
         types::{
             bitboard::BB,
-            board_state::BoardState,
+            board_state::{BoardState, Undo},
             chess_move::{
                 Move,
                 MoveType::{self, *},
@@ -612,6 +584,39 @@ pub mod test {
         },
     };
 
+    /// Positions with castling, en passant and promotions within a few plies, for `walk`
+    pub const WALK_POSITIONS: [&str; 4] = [
+        fen::START,
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    ];
+
+    /// Makes every move up to `depth` plies below `board`, calling `check` with the position before
+    /// and after each one, and asserts that unmaking it restores the position
+    pub fn walk(
+        gen: &mut MoveGen,
+        board: &mut BoardState,
+        depth: i32,
+        check: &mut impl FnMut(&BoardState, &Move, &BoardState),
+    ) {
+        if depth == 0 {
+            return;
+        }
+
+        let before: BoardState = *board;
+
+        for mv in gen.all_moves(board).unwrap() {
+            let undo: Undo = board.make_move(&mv).unwrap();
+            check(&before, &mv, board);
+
+            walk(gen, board, depth - 1, check);
+
+            board.unmake_move(&undo);
+            assert_eq!(*board, before, "{mv}");
+        }
+    }
+
     #[allow(dead_code)]
     fn make_move(to: Square, from: Square) -> Move {
         Move {
@@ -1240,6 +1245,37 @@ pub mod test {
         assert_eq!(list.len(), 2);
     }
 
+    #[test]
+    fn capture_generation_matches_filtered_moves() {
+        let mut gen: MoveGen = MoveGen::default();
+
+        for notation in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/8/3p4/KPp4r/5R1k/8/8/8 w - c6 0 1",
+            "3r4/4P3/8/8/8/8/k7/4K3 w - - 0 1",
+        ] {
+            let board: BoardState = fen::parse(notation).ok().unwrap();
+            let expected: Vec<Move> = gen
+                .all_moves(&board)
+                .unwrap()
+                .into_iter()
+                .filter(|mv| {
+                    board.at(mv.to).unwrap().is_some()
+                        || mv.kind == EnPassantCapture
+                        || mv.is_promotion()
+                })
+                .collect();
+            let captures: Vec<Move> = gen.captures(&board).unwrap();
+
+            assert_eq!(captures.len(), expected.len(), "{notation}");
+            assert!(
+                expected.iter().all(|mv| captures.contains(mv)),
+                "{notation}"
+            );
+        }
+    }
+
     #[test]
     fn king_move_removed() {
         let board = fen::parse("rnbqkb1r/1ppppppp/p6n/8/8/3P4/PPP1PPPP/RN1QKBNR w KQkq - 1 2")
@@ -0,0 +1,606 @@
+use std::{
+    fmt::{Display, Write},
+    iter::Peekable,
+    str::Chars,
+    vec::IntoIter,
+};
+
+use crate::{
+    fen,
+    movegen::MoveGen,
+    types::{board_state::BoardState, chess_move::Move, colour::Colour, game::Game, EngineError},
+};
+
+/// Lines of movetext are wrapped before this many characters
+const LINE_WIDTH: usize = 80;
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+/// Move suffix annotations and the NAGs they stand for
+const SUFFIXES: [(&str, u8); 6] = [
+    ("!", 1),
+    ("?", 2),
+    ("!!", 3),
+    ("??", 4),
+    ("!?", 5),
+    ("?!", 6),
+];
+
+/// A game read from or written to PGN. `positions[i]` is the position `moves[i]` is played in,
+/// followed by the final position
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub positions: Vec<BoardState>,
+    pub moves: Vec<PgnMove>,
+    pub result: String,
+}
+
+/// A move in the movetext and the annotations following it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PgnMove {
+    pub mv: Move,
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+    /// Lines played instead of this move, from the same position
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+impl PgnMove {
+    fn new(mv: Move) -> Self {
+        Self {
+            mv,
+            nags: Vec::new(),
+            comment: None,
+            variations: Vec::new(),
+        }
+    }
+}
+
+/// Where in the input a game stopped making sense
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PgnError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl PgnError {
+    fn new(line: usize, column: usize, message: &str) -> Self {
+        Self {
+            line,
+            column,
+            message: String::from(message),
+        }
+    }
+}
+
+impl From<PgnError> for EngineError {
+    fn from(error: PgnError) -> Self {
+        EngineError(format!("[pgn] {error}"))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    VariationStart,
+    VariationEnd,
+    MoveNumber,
+    Result(String),
+    San(String),
+}
+
+/// A token and the line and column it starts at
+type Spanned = (Token, usize, usize);
+
+type Tokens = Peekable<IntoIter<Spanned>>;
+
+/// The moves of a line, the positions they are played in followed by the final position, and the
+/// result if the line ends the game
+type Line = (Vec<PgnMove>, Vec<BoardState>, Option<String>);
+
+struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl Lexer<'_> {
+    fn next_char(&mut self) -> Option<char> {
+        let c: char = self.chars.next()?;
+
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(c)
+    }
+
+    /// Reads up to and including `end`, returning everything before it
+    fn until(&mut self, end: char, line: usize, column: usize) -> Result<String, PgnError> {
+        let mut text: String = String::new();
+
+        loop {
+            match self.next_char() {
+                Some(c) if c == end => return Ok(text),
+                Some(c) => text.push(c),
+                None => return Err(PgnError::new(line, column, &format!("Missing '{end}'"))),
+            }
+        }
+    }
+
+    fn tag(&mut self, line: usize, column: usize) -> Result<Token, PgnError> {
+        let contents: String = self.until(']', line, column)?;
+        let contents: &str = contents.trim();
+
+        let Some((name, value)) = contents.split_once(char::is_whitespace) else {
+            return Err(PgnError::new(line, column, "Malformed tag pair"));
+        };
+        let value: &str = value.trim();
+
+        if !(value.len() >= 2 && value.starts_with('"') && value.ends_with('"')) {
+            return Err(PgnError::new(line, column, "Tag value must be quoted"));
+        }
+
+        let value: String = value[1..value.len() - 1]
+            .replace("\\\"", "\"")
+            .replace("\\\\", "\\");
+
+        Ok(Token::Tag(String::from(name), value))
+    }
+
+    /// Move numbers, results and SAN moves, which may be run together as in `1.e4`
+    fn symbol(&mut self, first: char, line: usize, column: usize, tokens: &mut Vec<Spanned>) {
+        let mut symbol: String = String::from(first);
+
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() || "{}();[$".contains(c) {
+                break;
+            }
+            symbol.push(c);
+            self.next_char();
+        }
+
+        if RESULTS.contains(&symbol.as_str()) {
+            tokens.push((Token::Result(symbol), line, column));
+            return;
+        }
+
+        let san: &str = if symbol.starts_with("0-0") {
+            &symbol
+        } else {
+            let number: &str = symbol.trim_start_matches(|c: char| c.is_ascii_digit());
+            let san: &str = number.trim_start_matches('.');
+
+            if san.len() != symbol.len() {
+                tokens.push((Token::MoveNumber, line, column));
+            }
+
+            san
+        };
+
+        if !san.is_empty() {
+            let offset: usize = symbol.len() - san.len();
+            tokens.push((Token::San(String::from(san)), line, column + offset));
+        }
+    }
+
+    fn tokens(mut self) -> Result<Vec<Spanned>, PgnError> {
+        let mut tokens: Vec<Spanned> = Vec::new();
+        let mut line_start: bool = true;
+
+        while let Some(&c) = self.chars.peek() {
+            let (line, column) = (self.line, self.column);
+
+            // A '%' in the first column escapes the whole line
+            if line_start && c == '%' {
+                self.until('\n', line, column).ok();
+                continue;
+            }
+
+            self.next_char();
+            line_start = c == '\n';
+
+            match c {
+                c if c.is_whitespace() => {}
+                '[' => tokens.push((self.tag(line, column)?, line, column)),
+                '{' => {
+                    let comment: String = self.until('}', line, column)?;
+                    let comment: String = comment.split_whitespace().collect::<Vec<_>>().join(" ");
+                    tokens.push((Token::Comment(comment), line, column));
+                }
+                ';' => {
+                    let comment: String = self.until('\n', line, column).unwrap_or_default();
+                    line_start = true;
+                    tokens.push((Token::Comment(String::from(comment.trim())), line, column));
+                }
+                '$' => {
+                    let mut nag: String = String::new();
+
+                    while let Some(&c) = self.chars.peek().filter(|c| c.is_ascii_digit()) {
+                        nag.push(c);
+                        self.next_char();
+                    }
+
+                    let Ok(nag) = nag.parse::<u8>() else {
+                        return Err(PgnError::new(line, column, "Malformed NAG"));
+                    };
+                    tokens.push((Token::Nag(nag), line, column));
+                }
+                '(' => tokens.push((Token::VariationStart, line, column)),
+                ')' => tokens.push((Token::VariationEnd, line, column)),
+                c if c.is_ascii_alphanumeric() || c == '*' => {
+                    self.symbol(c, line, column, &mut tokens);
+                }
+                c => return Err(PgnError::new(line, column, &format!("Unexpected '{c}'"))),
+            }
+        }
+
+        Ok(tokens)
+    }
+}
+
+/// Reads every game in `text`. Moves are checked for legality, including those in variations.
+pub fn parse(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let lexer: Lexer = Lexer {
+        chars: text.chars().peekable(),
+        line: 1,
+        column: 1,
+    };
+    let mut tokens: Tokens = lexer.tokens()?.into_iter().peekable();
+    let mut games: Vec<PgnGame> = Vec::new();
+
+    while tokens.peek().is_some() {
+        let mut tags: Vec<(String, String)> = Vec::new();
+        let mut start: BoardState = fen::parse(fen::START).expect("the start position is valid");
+
+        while let Some((Token::Tag(name, value), line, column)) =
+            tokens.next_if(|(token, ..)| matches!(token, Token::Tag(..)))
+        {
+            if name == "FEN" {
+                start = fen::parse(&value).map_err(|e: EngineError| PgnError {
+                    line,
+                    column,
+                    message: e.0,
+                })?;
+            }
+
+            tags.push((name, value));
+        }
+
+        let (moves, positions, result) = read_line(&mut tokens, start, None)?;
+
+        let mut game: PgnGame = PgnGame {
+            tags,
+            positions,
+            moves,
+            result: result.unwrap_or_else(|| String::from("*")),
+        };
+
+        if game.result == "*" {
+            if let Some((_, result)) = game.tags.iter().find(|(name, _)| name == "Result") {
+                game.result.clone_from(result);
+            }
+        }
+
+        games.push(game);
+    }
+
+    Ok(games)
+}
+
+/// Reads the moves played from `start` up to the result ending the game, or the ')' closing the
+/// variation opened at `open`
+fn read_line(
+    tokens: &mut Tokens,
+    start: BoardState,
+    open: Option<(usize, usize)>,
+) -> Result<Line, PgnError> {
+    let mut moves: Vec<PgnMove> = Vec::new();
+    let mut positions: Vec<BoardState> = vec![start];
+
+    while let Some((token, line, column)) = tokens.next() {
+        let error = |message: &str| PgnError::new(line, column, message);
+
+        match token {
+            Token::San(san) => {
+                let board: BoardState = positions[positions.len() - 1];
+                let notation: &str = san.trim_end_matches(['!', '?']);
+                let mv: Move =
+                    Move::from_san(notation, &board).map_err(|e: EngineError| error(&e.0))?;
+
+                positions.push(board.clone_with_move(&mv).map_err(|e| error(&e.0))?);
+                let mut node: PgnMove = PgnMove::new(mv);
+
+                let suffix: &str = &san[notation.len()..];
+                if !suffix.is_empty() {
+                    let Some((_, nag)) = SUFFIXES.iter().find(|(text, _)| *text == suffix) else {
+                        return Err(error(&format!("Unknown annotation '{suffix}'")));
+                    };
+                    node.nags.push(*nag);
+                }
+
+                moves.push(node);
+            }
+            Token::Comment(comment) => {
+                if let Some(last) = moves.last_mut() {
+                    last.comment = Some(match last.comment.take() {
+                        Some(previous) => format!("{previous} {comment}"),
+                        None => comment,
+                    });
+                }
+            }
+            Token::Nag(nag) => {
+                if let Some(last) = moves.last_mut() {
+                    last.nags.push(nag);
+                }
+            }
+            Token::VariationStart => {
+                if moves.is_empty() {
+                    return Err(error("Variation before any move"));
+                }
+
+                let board: BoardState = positions[positions.len() - 2];
+                let (variation, ..) = read_line(tokens, board, Some((line, column)))?;
+
+                if let Some(last) = moves.last_mut() {
+                    last.variations.push(variation);
+                }
+            }
+            Token::VariationEnd if open.is_some() => return Ok((moves, positions, None)),
+            Token::VariationEnd => return Err(error("Unmatched ')'")),
+            Token::Tag(..) => return Err(error("Tag pair inside movetext")),
+            Token::Result(_) if open.is_some() => return Err(error("Result inside a variation")),
+            Token::Result(result) => return Ok((moves, positions, Some(result))),
+            Token::MoveNumber => {}
+        }
+    }
+
+    match open {
+        Some((line, column)) => Err(PgnError::new(line, column, "Unterminated variation")),
+        None => Ok((moves, positions, None)),
+    }
+}
+
+impl PgnGame {
+    /// The moves of `game` with the Seven Tag Roster filled with placeholders
+    pub fn from_game(game: &Game, gen: &mut MoveGen) -> Result<Self, EngineError> {
+        let result: String = String::from(game.status(gen)?.result());
+        let mut tags: Vec<(String, String)> = ["Event", "Site", "Date", "Round", "White", "Black"]
+            .into_iter()
+            .map(|name: &str| (String::from(name), String::from("?")))
+            .collect();
+        tags.push((String::from("Result"), result.clone()));
+
+        let start_fen: String = fen::board_to_fen(&game.start)?;
+
+        if start_fen != fen::START {
+            tags.push((String::from("SetUp"), String::from("1")));
+            tags.push((String::from("FEN"), start_fen));
+        }
+
+        let mut positions: Vec<BoardState> = vec![game.start];
+
+        for mv in &game.moves {
+            positions.push(positions[positions.len() - 1].clone_with_move(mv)?);
+        }
+
+        Ok(Self {
+            tags,
+            positions,
+            moves: game.moves.iter().copied().map(PgnMove::new).collect(),
+            result,
+        })
+    }
+
+    pub fn to_game(&self) -> Result<Game, EngineError> {
+        let mut game: Game = Game::new(self.positions[0]);
+
+        for node in &self.moves {
+            game.make_move(&node.mv)?;
+        }
+
+        Ok(game)
+    }
+}
+
+/// Writes `game` in PGN export format, with movetext wrapped to 80 columns
+pub fn write(game: &PgnGame) -> Result<String, EngineError> {
+    let mut pgn: String = String::new();
+
+    for (name, value) in &game.tags {
+        let value: String = value.replace('\\', "\\\\").replace('"', "\\\"");
+        // Writing to a String can't fail
+        let _ = writeln!(pgn, "[{name} \"{value}\"]");
+    }
+
+    pgn.push('\n');
+
+    let mut words: Vec<String> = Vec::new();
+    write_line(&mut words, &game.moves, game.positions[0])?;
+    words.push(game.result.clone());
+
+    let mut line: String = String::new();
+
+    for word in words {
+        if !line.is_empty() && line.len() + 1 + word.len() > LINE_WIDTH {
+            pgn += &line;
+            pgn.push('\n');
+            line.clear();
+        }
+
+        if !line.is_empty() {
+            line.push(' ');
+        }
+
+        line += &word;
+    }
+
+    pgn += &line;
+    pgn += "\n\n";
+
+    Ok(pgn)
+}
+
+/// Appends the movetext of `moves` played from `board`, each move followed by its annotations and
+/// variations
+fn write_line(
+    words: &mut Vec<String>,
+    moves: &[PgnMove],
+    mut board: BoardState,
+) -> Result<(), EngineError> {
+    // Black's move needs its number at the start of a line, or after a comment or variation
+    let mut numbered: bool = false;
+
+    for node in moves {
+        if board.active_player == Colour::White {
+            words.push(format!("{}.", board.full_moves));
+        } else if !numbered {
+            words.push(format!("{}...", board.full_moves));
+        }
+
+        words.push(node.mv.to_san(&board)?);
+        words.extend(node.nags.iter().map(|nag: &u8| format!("${nag}")));
+
+        if let Some(comment) = &node.comment {
+            words.push(format!("{{{}}}", comment.replace('}', "")));
+        }
+
+        for variation in &node.variations {
+            let mut inner: Vec<String> = Vec::new();
+            write_line(&mut inner, variation, board)?;
+
+            if let Some(last) = inner.last_mut() {
+                last.push(')');
+                inner[0].insert(0, '(');
+                words.append(&mut inner);
+            }
+        }
+
+        numbered = node.comment.is_none() && node.variations.is_empty();
+        board = board.clone_with_move(&node.mv)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{fen, types::chess_move::Move};
+
+    use super::{parse, write, PgnError, PgnGame, PgnMove};
+
+    const GAMES: &str = r#"[Event "Casual \"blitz\""]
+[Site "?"]
+[Result "1-0"]
+
+1.e4 e5 2. Nf3 {The most common move} Nc6 $1 3. Bc4 (3. Bb5 a6 (3... Nf6) 4. Ba4)
+3... Nf6?! 4. Ng5 d5 5. exd5 Na5 6. Bb5+ c6 7. dxc6 bxc6 8. Qf3 ; rest of line
+cxb5 9. Qxa8 Qxa8 1-0
+
+[FEN "4k3/8/8/8/8/8/8/R3K3 b Q - 0 40"]
+[Result "*"]
+
+40... Kd7 41. O-O-O+ *
+"#;
+
+    #[test]
+    fn reports_positioned_errors() {
+        // Black's queen is blocked by the bishop on c8
+        let error: PgnError = parse(GAMES).unwrap_err();
+        assert_eq!((error.line, error.column), (7, 14), "{error}");
+
+        let error: PgnError = parse("[Event \"?\"]\n\n1. e4 {unterminated").unwrap_err();
+        assert_eq!((error.line, error.column), (3, 7), "{error}");
+
+        let error: PgnError = parse("1. e4 e5 )").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 10), "{error}");
+
+        // Moves inside variations are checked too, and variations must be closed
+        let error: PgnError = parse("1. e4 (1. d4 Ke7) e5").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 14), "{error}");
+
+        let error: PgnError = parse("1. e4 e5 (1... c5 2. Nf3").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 10), "{error}");
+
+        let error: PgnError = parse("[Event ?]").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 1), "{error}");
+    }
+
+    #[test]
+    fn round_trips_games() {
+        let text: String = GAMES.replace("Qxa8 Qxa8", "Qxa8");
+        let games: Vec<PgnGame> = parse(&text).unwrap();
+
+        assert_eq!(games.len(), 2);
+
+        let game: &PgnGame = &games[0];
+        assert_eq!(
+            game.tags[0],
+            (String::from("Event"), String::from("Casual \"blitz\""))
+        );
+        assert_eq!(game.moves.len(), 17);
+        assert_eq!(
+            game.moves[2].comment.as_deref(),
+            Some("The most common move")
+        );
+        assert_eq!(game.moves[14].comment.as_deref(), Some("rest of line"));
+
+        // Nc6 $1, Nf6?! and the nested variations replacing Bc4 and a6
+        assert_eq!(game.moves[3].nags, vec![1]);
+        assert_eq!(game.moves[5].nags, vec![6]);
+        let variation: &Vec<PgnMove> = &game.moves[4].variations[0];
+        assert_eq!(variation.len(), 3);
+        assert_eq!(variation[1].variations[0][0].mv.to_string(), "g8f6");
+        assert_eq!(game.result, "1-0");
+        assert_eq!(
+            fen::board_to_fen(&game.positions[17]).unwrap(),
+            "Q1bqkb1r/p4ppp/5n2/np2p1N1/8/8/PPPP1PPP/RNB1K2R b KQk - 0 9"
+        );
+
+        let endgame: &PgnGame = &games[1];
+        assert_eq!(endgame.moves.len(), 2);
+        assert_eq!(endgame.moves[1].mv.to_string(), "e1c1");
+        assert_eq!(endgame.result, "*");
+
+        for game in &games {
+            let written: String = write(game).unwrap();
+            assert!(
+                written.lines().all(|line: &str| line.len() <= 80),
+                "{written}"
+            );
+            assert_eq!(parse(&written).unwrap(), vec![game.clone()], "{written}");
+        }
+
+        let written: String = write(&games[0]).unwrap();
+        let movetext: String = written.split_whitespace().collect::<Vec<_>>().join(" ");
+        assert!(
+            movetext
+                .contains("2... Nc6 $1 3. Bc4 (3. Bb5 a6 (3... Nf6) 4. Ba4) 3... Nf6 $6 4. Ng5"),
+            "{written}"
+        );
+
+        let written: String = write(&games[1]).unwrap();
+        assert!(
+            written.ends_with("\n\n40... Kd7 41. O-O-O+ *\n\n"),
+            "{written}"
+        );
+
+        assert_eq!(
+            Move::from_san("Nf3", &games[0].positions[2]).unwrap(),
+            games[0].moves[2].mv
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use crate::{
+    movegen::lookup::Lookup,
+    types::{
+        bitboard::{PieceItr, Shift, BB},
+        board_state::BoardState,
+        colour::Colour::{self, *},
+        piece_type::PieceType::{self, *},
+        square::SquareIndex,
+        FILEA,
+    },
+};
+
+use super::{pawns::forward, Score, CENTIPAWN};
+
+// King safety terms in centipawns. Shelter and storm are indexed by how many ranks in front of the
+// king the nearest pawn on a file is, 0 when there is none.
+
+const SHELTER: [i32; 8] = [0, 15, 8, 3, 0, 0, 0, 0];
+const STORM: [i32; 8] = [0, -10, -25, -15, -5, 0, 0, 0];
+
+/// A file next to the king without any of our pawns, and one without any pawns at all
+const SEMI_OPEN_FILE: i32 = -15;
+const OPEN_FILE: i32 = -25;
+
+/// Attack units per king zone square attacked by each piece
+const ATTACK_UNITS: [(PieceType, i32); 4] = [(Knight, 2), (Bishop, 2), (Rook, 3), (Queen, 5)];
+
+/// The penalty for a number of attack units. The steps between entries grow by one every three
+/// units, as one or two attacked squares are rarely dangerous, up to 12 a unit until it levels off.
+#[rustfmt::skip]
+const SAFETY: [i32; 100] = [
+      0,   0,   1,   2,   3,   5,   7,   9,  12,  15,
+     18,  22,  26,  30,  35,  40,  45,  51,  57,  63,
+     70,  77,  84,  92, 100, 108, 117, 126, 135, 145,
+    155, 165, 176, 187, 198, 210, 222, 234, 246, 258,
+    270, 282, 294, 306, 318, 330, 342, 354, 366, 378,
+    390, 402, 414, 426, 438, 450, 462, 474, 486, 498,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+];
+
+/// The safety of `colour`'s king. It only matters while there are pieces left to attack it, so
+/// this is a middlegame term.
+pub fn safety(lookup: &Lookup, board: &BoardState, colour: Colour) -> Score {
+    let king: SquareIndex = board.position.bb(colour, King).trailing_zeros() as SquareIndex;
+
+    Score(
+        (shelter(board, king, colour) - attack(lookup, board, king, colour)) * CENTIPAWN,
+        0,
+    )
+}
+
+/// The pawn shield and storm on the king's file and those next to it, and how open those files are
+fn shelter(board: &BoardState, king: SquareIndex, colour: Colour) -> i32 {
+    let ours: BB = board.position.bb(colour, Pawn);
+    let theirs: BB = board.position.bb(!colour, Pawn);
+    let ahead: BB = ahead(king, colour);
+    let file: i32 = (king % 8) as i32;
+
+    let mut score: i32 = 0;
+
+    for file in (file - 1).max(0)..=(file + 1).min(7) {
+        let mask: BB = FILEA << file;
+
+        score += SHELTER[distance(ours & mask & ahead, king, colour)];
+        score += STORM[distance(theirs & mask & ahead, king, colour)];
+
+        if (ours | theirs) & mask == 0 {
+            score += OPEN_FILE;
+        } else if ours & mask == 0 {
+            score += SEMI_OPEN_FILE;
+        }
+    }
+
+    score
+}
+
+/// The penalty for the enemy pieces attacking the squares around the king, when at least two of
+/// them join in
+fn attack(lookup: &Lookup, board: &BoardState, king: SquareIndex, colour: Colour) -> i32 {
+    let occupied: BB = board.position.bb_all();
+    let around: BB = lookup.moves(king, King) | 1 << king;
+    let zone: BB = around | around.shift(forward(colour));
+
+    let mut attackers: i32 = 0;
+    let mut units: i32 = 0;
+
+    for (piece, weight) in ATTACK_UNITS {
+        for square in board.position.bb(!colour, piece).iter() {
+            let attacks: BB = match piece {
+                Knight => lookup.moves(square, Knight),
+                _ => lookup.sliding_moves(square, occupied, piece),
+            };
+
+            if attacks & zone != 0 {
+                attackers += 1;
+                units += weight * (attacks & zone).count_ones() as i32;
+            }
+        }
+    }
+
+    if attackers < 2 {
+        return 0;
+    }
+
+    SAFETY[units.min(99) as usize]
+}
+
+/// The ranks in front of `king`
+fn ahead(king: SquareIndex, colour: Colour) -> BB {
+    let rank: u64 = king / 8;
+
+    match colour {
+        White => (!0 as BB).checked_shl(8 * (rank as u32 + 1)).unwrap_or(0),
+        Black => (1 << (8 * rank)) - 1,
+    }
+}
+
+/// How many ranks in front of `king` the nearest of `pawns` is, 0 without any
+fn distance(pawns: BB, king: SquareIndex, colour: Colour) -> usize {
+    if pawns == 0 {
+        return 0;
+    }
+
+    let nearest: u64 = match colour {
+        White => u64::from(pawns.trailing_zeros()),
+        Black => 63 - u64::from(pawns.leading_zeros()),
+    };
+
+    (nearest / 8).abs_diff(king / 8) as usize
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{fen, movegen::lookup::Lookup};
+
+    use super::{attack, safety, shelter, Colour::*, SAFETY};
+
+    #[test]
+    fn scores_shelter_and_attacks() {
+        let lookup: Lookup = Lookup::default();
+
+        // An intact shield is better than an advanced one, which is better than an open file
+        let intact = fen::parse("6k1/8/8/8/8/8/5PPP/6K1 w - - 0 1").unwrap();
+        let advanced = fen::parse("6k1/8/8/8/8/6P1/5P1P/6K1 w - - 0 1").unwrap();
+        let open = fen::parse("6k1/8/8/8/8/8/5P1P/6K1 w - - 0 1").unwrap();
+        assert!(shelter(&intact, 6, White) > shelter(&advanced, 6, White));
+        assert!(shelter(&advanced, 6, White) > shelter(&open, 6, White));
+
+        // Black's shelter mirrors White's
+        let mirrored = fen::parse("6k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        assert_eq!(shelter(&mirrored, 62, Black), shelter(&intact, 6, White));
+
+        // A storming pawn hurts more than the open file it half closes
+        let storm = fen::parse("6k1/8/8/8/8/6p1/5P1P/6K1 w - - 0 1").unwrap();
+        assert!(shelter(&storm, 6, White) < shelter(&open, 6, White));
+
+        // A lone attacker is ignored, a queen and knight together are not
+        let queen = fen::parse("6k1/8/8/8/7q/8/5PPP/6K1 w - - 0 1").unwrap();
+        let queen_knight = fen::parse("6k1/8/8/8/6nq/8/5PPP/6K1 w - - 0 1").unwrap();
+        assert_eq!(attack(&lookup, &queen, 6, White), 0);
+        assert!(attack(&lookup, &queen_knight, 6, White) > 0);
+        assert!(safety(&lookup, &queen_knight, White).0 < safety(&lookup, &queen, White).0);
+
+        // The penalty never falls as attack units are added, and never jumps
+        for (units, pair) in SAFETY.windows(2).enumerate() {
+            let step: i32 = pair[1] - pair[0];
+            assert!((0..=12).contains(&step), "{units}: {step}");
+        }
+    }
+}
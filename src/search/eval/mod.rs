@@ -0,0 +1,511 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    ops::{Add, AddAssign, Mul, Sub, SubAssign},
+};
+
+use self::pawns::{PawnEntry, PawnTable};
+
+use crate::{
+    movegen::lookup::Lookup,
+    types::{
+        bitboard::PieceItr,
+        board_state::BoardState,
+        colour::Colour::{self, *},
+        piece_type::PieceType::{self, *},
+    },
+};
+
+mod king;
+mod pawns;
+mod pieces;
+
+// Piece-square tables in centipawns, laid out as the board is seen from White's side, rank 8 first
+
+#[rustfmt::skip]
+const PAWN_MG: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    50,  50,  50,  50,  50,  50,  50,  50,
+    10,  10,  20,  30,  30,  20,  10,  10,
+     5,   5,  10,  25,  25,  10,   5,   5,
+     0,   0,   0,  20,  20,   0,   0,   0,
+     5,  -5, -10,   0,   0, -10,  -5,   5,
+     5,  10,  10, -20, -20,  10,  10,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const PAWN_EG: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    40,  40,  40,  40,  40,  40,  40,  40,
+    25,  25,  25,  25,  25,  25,  25,  25,
+    15,  15,  15,  15,  15,  15,  15,  15,
+    10,  10,  10,  10,  10,  10,  10,  10,
+     5,   5,   5,   5,   5,   5,   5,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_MG: [i32; 64] = [
+   -50, -40, -30, -30, -30, -30, -40, -50,
+   -40, -20,   0,   0,   0,   0, -20, -40,
+   -30,   0,  10,  15,  15,  10,   0, -30,
+   -30,   5,  15,  20,  20,  15,   5, -30,
+   -30,   0,  15,  20,  20,  15,   0, -30,
+   -30,   5,  10,  15,  15,  10,   5, -30,
+   -40, -20,   0,   5,   5,   0, -20, -40,
+   -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+#[rustfmt::skip]
+const KNIGHT_EG: [i32; 64] = [
+   -40, -30, -20, -20, -20, -20, -30, -40,
+   -30, -15,  -5,   0,   0,  -5, -15, -30,
+   -20,  -5,  10,  15,  15,  10,  -5, -20,
+   -20,   0,  15,  20,  20,  15,   0, -20,
+   -20,   0,  15,  20,  20,  15,   0, -20,
+   -20,  -5,  10,  15,  15,  10,  -5, -20,
+   -30, -15,  -5,   0,   0,  -5, -15, -30,
+   -40, -30, -20, -20, -20, -20, -30, -40,
+];
+
+#[rustfmt::skip]
+const BISHOP_MG: [i32; 64] = [
+   -20, -10, -10, -10, -10, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,  10,  10,   5,   0, -10,
+   -10,   5,   5,  10,  10,   5,   5, -10,
+   -10,   0,  10,  10,  10,  10,   0, -10,
+   -10,  10,  10,  10,  10,  10,  10, -10,
+   -10,   5,   0,   0,   0,   0,   5, -10,
+   -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const BISHOP_EG: [i32; 64] = [
+   -15, -10, -10, -10, -10, -10, -10, -15,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,   5,   5,   5,   0, -10,
+   -10,   0,   5,  10,  10,   5,   0, -10,
+   -10,   0,   5,  10,  10,   5,   0, -10,
+   -10,   0,   5,   5,   5,   5,   0, -10,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -15, -10, -10, -10, -10, -10, -10, -15,
+];
+
+#[rustfmt::skip]
+const ROOK_MG: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+     5,  10,  10,  10,  10,  10,  10,   5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+     0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const ROOK_EG: [i32; 64] = [
+     5,   5,   5,   5,   5,   5,   5,   5,
+    10,  10,  10,  10,  10,  10,  10,  10,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const QUEEN_MG: [i32; 64] = [
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,   5,   5,   5,   0, -10,
+    -5,   0,   5,   5,   5,   5,   0,  -5,
+     0,   0,   5,   5,   5,   5,   0,  -5,
+   -10,   5,   5,   5,   5,   5,   0, -10,
+   -10,   0,   5,   0,   0,   0,   0, -10,
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const QUEEN_EG: [i32; 64] = [
+   -20, -10, -10, -10, -10, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,  10,  10,  10,  10,   0, -10,
+   -10,   0,  10,  15,  15,  10,   0, -10,
+   -10,   0,  10,  15,  15,  10,   0, -10,
+   -10,   0,  10,  10,  10,  10,   0, -10,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const KING_MG: [i32; 64] = [
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -20, -30, -30, -40, -40, -30, -30, -20,
+   -10, -20, -20, -20, -20, -20, -20, -10,
+    20,  20,   0,   0,   0,   0,  20,  20,
+    20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+#[rustfmt::skip]
+const KING_EG: [i32; 64] = [
+   -50, -40, -30, -20, -20, -30, -40, -50,
+   -30, -20, -10,   0,   0, -10, -20, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -30,   0,   0,   0,   0, -30, -30,
+   -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+pub const PAWN_VALUE: u32 = 1000;
+const KNIGHT_VALUE: u32 = 3000;
+const BISHOP_VALUE: u32 = 3200;
+const ROOK_VALUE: u32 = 5000;
+const QUEEN_VALUE: u32 = 9000;
+
+const CENTIPAWN: i32 = PAWN_VALUE as i32 / 100;
+
+// Game phase weights: the phase is the weighted count of the pieces left on the board, from
+// `MAX_PHASE` in the opening down to 0 with only kings and pawns
+const KNIGHT_PHASE: i32 = 10;
+const BISHOP_PHASE: i32 = 10;
+const ROOK_PHASE: i32 = 20;
+const QUEEN_PHASE: i32 = 45;
+pub const MAX_PHASE: i32 = 2 * (2 * KNIGHT_PHASE + 2 * BISHOP_PHASE + 2 * ROOK_PHASE + QUEEN_PHASE);
+
+/// A middlegame and an endgame score, from White's point of view
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Score(pub i32, pub i32);
+
+impl Score {
+    /// Interpolates between the middlegame and endgame scores by `phase`
+    pub fn taper(self, phase: i32) -> i32 {
+        (self.0 * phase + self.1 * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl Add for Score {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Score(self.0 + other.0, self.1 + other.1)
+    }
+}
+
+impl Sub for Score {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Score(self.0 - other.0, self.1 - other.1)
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Score {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+impl Mul<i32> for Score {
+    type Output = Self;
+
+    fn mul(self, factor: i32) -> Self {
+        Score(self.0 * factor, self.1 * factor)
+    }
+}
+
+pub fn piece_value(piece: PieceType) -> i32 {
+    (match piece {
+        Pawn => PAWN_VALUE,
+        Knight => KNIGHT_VALUE,
+        Bishop => BISHOP_VALUE,
+        Rook => ROOK_VALUE,
+        Queen => QUEEN_VALUE,
+        King => 0,
+    }) as i32
+}
+
+/// The names of the evaluation terms, in the order `Evaluator::terms` scores them
+const TERMS: [&str; 6] = [
+    "Material",
+    "Placement",
+    "Pawns",
+    "Mobility",
+    "Pieces",
+    "King safety",
+];
+
+/// Evaluates positions, keeping the pawn structure of those it has seen
+#[derive(Default)]
+pub struct Evaluator {
+    lookup: Lookup,
+    pawns: PawnTable,
+}
+
+impl Evaluator {
+    pub fn clear(&mut self) {
+        self.pawns.clear();
+    }
+
+    /// The score of `board` for the side to move
+    pub fn eval(&mut self, board: &BoardState) -> i32 {
+        let score: Score = total(&self.terms(board));
+        let eval: i32 = score.taper(phase(board));
+
+        if board.active_player == White {
+            eval
+        } else {
+            -eval
+        }
+    }
+
+    /// Every term of the evaluation of `board`, the breakdown of `Evaluator::eval` shown by the
+    /// `eval` command
+    pub fn trace(&mut self, board: &BoardState) -> Trace {
+        let terms: [[Score; 2]; TERMS.len()] = self.terms(board);
+        let phase: i32 = phase(board);
+
+        Trace {
+            terms: std::array::from_fn(|i: usize| (TERMS[i], terms[i])),
+            phase,
+            eval: total(&terms).taper(phase),
+        }
+    }
+
+    /// The scores of each term for White and Black
+    fn terms(&mut self, board: &BoardState) -> [[Score; 2]; TERMS.len()] {
+        let pawns: PawnEntry = self.pawns.probe(board);
+        let lookup: &Lookup = &self.lookup;
+
+        [
+            both(|colour: Colour| get_material_score(board, colour)),
+            both(|colour: Colour| get_piece_eval(board, colour)),
+            both(|colour: Colour| pawns.score[colour as usize] + pawns.free_passers(board, colour)),
+            both(|colour: Colour| pieces::mobility(lookup, board, colour)),
+            both(|colour: Colour| pieces::activity(lookup, board, colour)),
+            both(|colour: Colour| king::safety(lookup, board, colour)),
+        ]
+    }
+}
+
+fn both(term: impl Fn(Colour) -> Score) -> [Score; 2] {
+    [term(White), term(Black)]
+}
+
+/// The sum of the terms from White's point of view
+fn total(terms: &[[Score; 2]]) -> Score {
+    terms
+        .iter()
+        .fold(Score::default(), |total: Score, [white, black]| {
+            total + *white - *black
+        })
+}
+
+/// An evaluation broken down by term and colour
+#[derive(Debug, Clone, Copy)]
+pub struct Trace {
+    pub terms: [(&'static str, [Score; 2]); TERMS.len()],
+    pub phase: i32,
+    /// The final score from White's point of view
+    pub eval: i32,
+}
+
+impl Display for Trace {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let pawns =
+            |value: i32| -> String { format!("{:+6.2}", f64::from(value) / f64::from(PAWN_VALUE)) };
+        let cell = |score: Score| -> String { format!("{} {}", pawns(score.0), pawns(score.1)) };
+        let line: &str = "--------------+---------------+---------------+--------------";
+
+        writeln!(
+            f,
+            "         Term |     White     |     Black     |     Total"
+        )?;
+        writeln!(
+            f,
+            "              |   MG     EG   |   MG     EG   |   MG     EG"
+        )?;
+        writeln!(f, "{line}")?;
+
+        for (name, [white, black]) in self.terms {
+            writeln!(
+                f,
+                "{name:>13} | {} | {} | {}",
+                cell(white),
+                cell(black),
+                cell(white - black)
+            )?;
+        }
+
+        let terms: Vec<[Score; 2]> = self.terms.iter().map(|(_, scores)| *scores).collect();
+
+        writeln!(f, "{line}")?;
+        writeln!(
+            f,
+            "{:>13} | {:13} | {:13} | {}",
+            "Total",
+            "",
+            "",
+            cell(total(&terms))
+        )?;
+        writeln!(f)?;
+        writeln!(f, "Phase: {} of {MAX_PHASE}", self.phase)?;
+        write!(
+            f,
+            "Final evaluation: {} (White side)",
+            pawns(self.eval).trim()
+        )
+    }
+}
+
+/// How far the game is from the endgame, `MAX_PHASE` with all pieces on the board. Promotions
+/// could take it higher, so it is capped.
+pub fn phase(board: &BoardState) -> i32 {
+    let count = |piece: PieceType| -> i32 { board.position.bb_piece(piece).count_ones() as i32 };
+
+    (count(Knight) * KNIGHT_PHASE
+        + count(Bishop) * BISHOP_PHASE
+        + count(Rook) * ROOK_PHASE
+        + count(Queen) * QUEEN_PHASE)
+        .min(MAX_PHASE)
+}
+
+fn get_material_score(board: &BoardState, colour: Colour) -> Score {
+    let material: i32 = [Pawn, Knight, Bishop, Rook, Queen]
+        .into_iter()
+        .map(|piece: PieceType| {
+            board.position.bb(colour, piece).count_ones() as i32 * piece_value(piece)
+        })
+        .sum();
+
+    Score(material, material)
+}
+
+fn get_piece_eval(board: &BoardState, colour: Colour) -> Score {
+    let mut eval: Score = Score::default();
+
+    for piece in [Pawn, Knight, Bishop, Rook, Queen, King] {
+        eval += eval_piece_type(board, piece, colour);
+    }
+
+    eval
+}
+
+fn eval_piece_type(board: &BoardState, piece: PieceType, colour: Colour) -> Score {
+    let (middlegame, endgame): (&[i32; 64], &[i32; 64]) = match piece {
+        Pawn => (&PAWN_MG, &PAWN_EG),
+        Knight => (&KNIGHT_MG, &KNIGHT_EG),
+        Bishop => (&BISHOP_MG, &BISHOP_EG),
+        Rook => (&ROOK_MG, &ROOK_EG),
+        Queen => (&QUEEN_MG, &QUEEN_EG),
+        King => (&KING_MG, &KING_EG),
+    };
+
+    let mut eval: Score = Score::default();
+
+    for sq in board.position.bb(colour, piece).iter() {
+        // The tables start from rank 8, so White's squares are flipped vertically
+        let index: usize = match colour {
+            White => sq as usize ^ 0b111_000,
+            Black => sq as usize,
+        };
+
+        eval += Score(middlegame[index], endgame[index]);
+    }
+
+    eval * CENTIPAWN
+}
+
+// fn mop_up_eval(&self, colour: Colour,  our_material: MaterialInfo,  their_material: MaterialInfo) -> i32
+// {
+//     if our_material.material_score > their_material.material_score + PAWN_VALUE * 2 && their_material.endgame_t > 0.0
+//     {
+//         let mut mop_up_score: i32 = 0;
+
+//         let our_king_square: SquareIndex = self.0.position.bb(colour, King).trailing_zeros() as SquareIndex;
+//         let their_king_square: SquareIndex = self.0.position.bb(colour, King).trailing_zeros() as SquareIndex;
+
+//         mop_up_score += (14 - PrecomputedMoveData.OrthogonalDistance[our_king_square, their_king_square]) * 4;
+//         mop_up_score +=
+
+//         mop_up_score += PrecomputedMoveData.CentreManhattanDistance[opponent_king_square] * 10;
+//         return (mop_up_score as f32 * their_material.endgame_t) as i32;
+//     }
+
+//      0
+// }
+
+#[cfg(test)]
+mod test {
+    use crate::fen;
+
+    use crate::types::board_state::BoardState;
+
+    use super::{phase, Evaluator, Score, Trace, MAX_PHASE, PAWN_VALUE};
+
+    fn eval(board: &BoardState) -> i32 {
+        Evaluator::default().eval(board)
+    }
+
+    #[test]
+    fn tapers_from_middlegame_to_endgame() {
+        assert_eq!(phase(&fen::parse(fen::START).unwrap()), MAX_PHASE);
+        assert_eq!(eval(&fen::parse(fen::START).unwrap()), 0);
+
+        // Mirrored positions score the same for the side to move
+        assert_eq!(
+            eval(&fen::parse("r3k2r/ppp2ppp/2n5/3qp3/8/2N5/PPPQ1PPP/R3K2R w KQkq - 0 1").unwrap()),
+            eval(&fen::parse("r3k2r/pppq1ppp/2n5/8/3QP3/2N5/PPP2PPP/R3K2R b KQkq - 0 1").unwrap())
+        );
+
+        // With bare kings the king belongs in the centre
+        let endgame: i32 = eval(&fen::parse("8/8/8/8/3K4/8/8/6k1 w - - 0 1").unwrap());
+        assert_eq!(
+            phase(&fen::parse("8/8/8/8/3K4/8/8/6k1 w - - 0 1").unwrap()),
+            0
+        );
+        assert!(endgame > 0, "{endgame}");
+
+        assert_eq!(Score(300, 100).taper(MAX_PHASE), 300);
+        assert_eq!(Score(300, 100).taper(MAX_PHASE / 2), 200);
+        assert_eq!(Score(300, 100).taper(0), 100);
+    }
+
+    #[test]
+    fn traces_each_term() {
+        let board: BoardState =
+            fen::parse("r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 5 4")
+                .unwrap();
+        let trace: Trace = Evaluator::default().trace(&board);
+
+        // Black is to move, the trace is from White's side
+        assert_eq!(trace.eval, -eval(&board));
+        assert_eq!(trace.phase, MAX_PHASE);
+
+        let (name, [white, black]) = trace.terms[0];
+        assert_eq!(name, "Material");
+        assert_eq!(white, black);
+
+        let shown: String = trace.to_string();
+        assert!(shown.contains("  King safety |"), "{shown}");
+        let last: String = format!(
+            "Final evaluation: {:+.2} (White side)",
+            f64::from(trace.eval) / f64::from(PAWN_VALUE)
+        );
+        assert!(shown.ends_with(&last), "{shown}");
+    }
+}
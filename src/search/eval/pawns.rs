@@ -0,0 +1,251 @@
+use crate::types::{
+    bitboard::{PieceItr, Shift, ToBitboard, BB},
+    board_state::BoardState,
+    colour::Colour::{self, *},
+    piece_type::PieceType::Pawn,
+    zobrist::ZobristKey,
+    EAST, NORTH, SOUTH, WEST,
+};
+
+use super::{Score, CENTIPAWN};
+
+const TABLE_SIZE: usize = 1 << 14;
+
+// Pawn structure terms in centipawns, the arrays are indexed by rank counted from the pawn's side
+
+const DOUBLED: Score = Score(-10, -20);
+const ISOLATED: Score = Score(-10, -15);
+const BACKWARD: Score = Score(-8, -10);
+
+#[rustfmt::skip]
+const CONNECTED: [Score; 8] = [
+    Score(0, 0), Score(3, 3), Score(5, 5), Score(8, 8),
+    Score(12, 12), Score(20, 20), Score(30, 30), Score(0, 0),
+];
+
+#[rustfmt::skip]
+const PASSED: [Score; 8] = [
+    Score(0, 0), Score(5, 10), Score(10, 15), Score(15, 25),
+    Score(30, 50), Score(50, 90), Score(80, 140), Score(0, 0),
+];
+
+/// Added to `PASSED` when nothing stands between the pawn and its promotion square
+#[rustfmt::skip]
+const FREE_PATH: [Score; 8] = [
+    Score(0, 0), Score(0, 0), Score(0, 5), Score(5, 10),
+    Score(10, 25), Score(20, 45), Score(35, 70), Score(0, 0),
+];
+
+/// The pawn structure terms of a position, which only depend on where the pawns are
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PawnEntry {
+    key: ZobristKey,
+    /// Pawn structure scores by colour
+    pub score: [Score; 2],
+    /// Passed pawns by colour
+    pub passed: [BB; 2],
+}
+
+/// Pawn structure evaluations by pawn key. Pawns move rarely, so most lookups are hits.
+pub struct PawnTable {
+    entries: Vec<Option<PawnEntry>>,
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        Self {
+            entries: vec![None; TABLE_SIZE],
+        }
+    }
+}
+
+impl PawnTable {
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+
+    /// The pawn structure of `board`, evaluated unless it has been seen before
+    pub fn probe(&mut self, board: &BoardState) -> PawnEntry {
+        let slot: &mut Option<PawnEntry> =
+            &mut self.entries[board.pawn_key as usize & (TABLE_SIZE - 1)];
+
+        match slot {
+            Some(entry) if entry.key == board.pawn_key => *entry,
+            _ => *slot.insert(PawnEntry::new(board)),
+        }
+    }
+}
+
+impl PawnEntry {
+    fn new(board: &BoardState) -> Self {
+        let (white, white_passed) = structure(board, White);
+        let (black, black_passed) = structure(board, Black);
+
+        Self {
+            key: board.pawn_key,
+            score: [white, black],
+            passed: [white_passed, black_passed],
+        }
+    }
+
+    /// The bonus for `colour`'s passed pawns with a free path, which depends on the other pieces too
+    pub fn free_passers(&self, board: &BoardState, colour: Colour) -> Score {
+        let occupied: BB = board.position.bb_all();
+        let mut score: Score = Score::default();
+
+        for square in self.passed[colour as usize].iter() {
+            if front_span(square.to_bitboard(), forward(colour)) & occupied == 0 {
+                score += FREE_PATH[relative_rank(square, colour)];
+            }
+        }
+
+        score * CENTIPAWN
+    }
+}
+
+/// One side's pawns by the structure terms that apply to them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PawnFeatures {
+    /// Pawns no enemy pawn can stop, only the front one of doubled pawns counts
+    passed: BB,
+    /// Pawns with another of ours in front of them
+    doubled: BB,
+    isolated: BB,
+    /// Pawns whose stop square is attacked by an enemy pawn and can never be defended by ours
+    backward: BB,
+    /// Pawns defended by a pawn or standing next to one
+    connected: BB,
+}
+
+impl PawnFeatures {
+    fn new(board: &BoardState, colour: Colour) -> Self {
+        let ours: BB = board.position.bb(colour, Pawn);
+        let theirs: BB = board.position.bb(!colour, Pawn);
+        let forward: i8 = forward(colour);
+
+        let their_spans: BB = front_span(theirs, -forward);
+        let doubled: BB = ours & front_span(ours, -forward);
+        let isolated: BB = ours & !file_fill(sideways(ours));
+
+        let stops: BB = ours.shift(forward);
+        let our_attack_spans: BB = fill(attacks(ours, forward), forward);
+
+        Self {
+            passed: ours & !(their_spans | sideways(their_spans)) & !doubled,
+            doubled,
+            isolated,
+            backward: (stops & attacks(theirs, -forward) & !our_attack_spans).shift(-forward)
+                & !isolated,
+            connected: ours & (attacks(ours, forward) | sideways(ours)),
+        }
+    }
+}
+
+/// The pawn structure score of one side and its passed pawns
+fn structure(board: &BoardState, colour: Colour) -> (Score, BB) {
+    let features: PawnFeatures = PawnFeatures::new(board, colour);
+
+    let mut score: Score = DOUBLED * features.doubled.count_ones() as i32
+        + ISOLATED * features.isolated.count_ones() as i32
+        + BACKWARD * features.backward.count_ones() as i32;
+
+    for square in features.connected.iter() {
+        score += CONNECTED[relative_rank(square, colour)];
+    }
+
+    for square in features.passed.iter() {
+        score += PASSED[relative_rank(square, colour)];
+    }
+
+    (score * CENTIPAWN, features.passed)
+}
+
+pub fn forward(colour: Colour) -> i8 {
+    match colour {
+        White => NORTH,
+        Black => SOUTH,
+    }
+}
+
+pub fn relative_rank(square: u64, colour: Colour) -> usize {
+    match colour {
+        White => square as usize / 8,
+        Black => 7 - square as usize / 8,
+    }
+}
+
+/// `bb` smeared along its files in the `direction` of `NORTH` or `SOUTH`, including itself
+pub fn fill(bb: BB, direction: i8) -> BB {
+    let mut bb: BB = bb;
+    bb |= bb.shift(direction);
+    bb |= bb.shift(2 * direction);
+    bb |= bb.shift(4 * direction);
+    bb
+}
+
+/// The squares in front of each of `bb` on its file
+fn front_span(bb: BB, forward: i8) -> BB {
+    fill(bb, forward).shift(forward)
+}
+
+fn file_fill(bb: BB) -> BB {
+    fill(bb, NORTH) | fill(bb, SOUTH)
+}
+
+fn sideways(bb: BB) -> BB {
+    bb.shift(EAST) | bb.shift(WEST)
+}
+
+pub fn attacks(pawns: BB, forward: i8) -> BB {
+    pawns.shift(forward + EAST) | pawns.shift(forward + WEST)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        fen,
+        types::{bitboard::BB, square::Square::*},
+    };
+
+    use super::{Colour::*, PawnFeatures, PawnTable};
+
+    fn squares(squares: &[crate::types::square::Square]) -> BB {
+        squares
+            .iter()
+            .fold(0, |bb: BB, &square| bb | 1 << square as u64)
+    }
+
+    #[test]
+    fn finds_pawn_structure_features() {
+        // White has an isolated passed a-pawn, a backward d-pawn supporting c4 and e4, and
+        // doubled isolated g-pawns. Black's pawns are both isolated, and blocked or held back.
+        let board = fen::parse("4k3/8/P7/4p2p/2P1P3/3P2P1/6P1/4K3 w - - 0 1").unwrap();
+
+        assert_eq!(
+            PawnFeatures::new(&board, White),
+            PawnFeatures {
+                passed: squares(&[A6, C4]),
+                doubled: squares(&[G2]),
+                isolated: squares(&[A6, G2, G3]),
+                backward: squares(&[D3]),
+                connected: squares(&[C4, E4]),
+            }
+        );
+        assert_eq!(
+            PawnFeatures::new(&board, Black),
+            PawnFeatures {
+                passed: 0,
+                doubled: 0,
+                isolated: squares(&[E5, H5]),
+                backward: 0,
+                connected: 0,
+            }
+        );
+
+        // A passed pawn is worth more the further it has come
+        let mut table: PawnTable = PawnTable::default();
+        let far = table.probe(&fen::parse("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap());
+        let near = table.probe(&fen::parse("4k3/8/8/8/8/P7/8/4K3 w - - 0 1").unwrap());
+        assert!(far.score[White as usize].1 > near.score[White as usize].1);
+    }
+}
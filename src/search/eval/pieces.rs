@@ -0,0 +1,212 @@
+use crate::{
+    movegen::lookup::Lookup,
+    types::{
+        bitboard::{PieceItr, ToBitboard, BB},
+        board_state::BoardState,
+        colour::Colour::{self, *},
+        piece_type::PieceType::{self, *},
+        square::{Square, Square::*, SquareIndex},
+        FILEA, RANK1,
+    },
+};
+
+use super::{
+    pawns::{attacks, fill, forward, relative_rank},
+    Score, CENTIPAWN,
+};
+
+// Piece activity terms in centipawns
+
+/// The bonus per safe square a piece can move to, counted from the number it usually has
+const MOBILITY: [(PieceType, Score, i32); 4] = [
+    (Knight, Score(4, 4), 4),
+    (Bishop, Score(5, 5), 6),
+    (Rook, Score(2, 4), 7),
+    (Queen, Score(1, 2), 13),
+];
+
+const BISHOP_PAIR: Score = Score(30, 50);
+const ROOK_OPEN_FILE: Score = Score(25, 10);
+const ROOK_SEMI_OPEN_FILE: Score = Score(12, 5);
+/// A rook on the 7th rank with enemy pawns on it or the enemy king on the 8th
+const ROOK_ON_SEVENTH: Score = Score(20, 30);
+/// A knight defended by a pawn on a square no enemy pawn can ever attack
+const KNIGHT_OUTPOST: Score = Score(25, 10);
+/// A bishop shut in on the 7th by a pawn, e.g. after taking on a7
+const TRAPPED_BISHOP: Score = Score(-100, -100);
+/// A rook hemmed in by its own king, which can no longer castle to free it
+const TRAPPED_ROOK: Score = Score(-40, -10);
+
+/// Bishop squares and the pawns that trap bishops there, from White's side
+const BISHOP_TRAPS: [(Square, Square); 2] = [(A7, B6), (H7, G6)];
+
+/// The mobility of `colour`'s pieces, counting the squares not held by our pieces or attacked by
+/// enemy pawns
+pub fn mobility(lookup: &Lookup, board: &BoardState, colour: Colour) -> Score {
+    let safe: BB = safe_squares(board, colour);
+    let mut score: Score = Score::default();
+
+    for (piece, bonus, usual) in MOBILITY {
+        for square in board.position.bb(colour, piece).iter() {
+            let moves: i32 = (moves(lookup, board, piece, square) & safe).count_ones() as i32;
+            score += bonus * (moves - usual);
+        }
+    }
+
+    score * CENTIPAWN
+}
+
+/// Bonuses for well placed pieces, and penalties for trapped ones
+pub fn activity(lookup: &Lookup, board: &BoardState, colour: Colour) -> Score {
+    let ours: BB = board.position.bb(colour, Pawn);
+    let theirs: BB = board.position.bb(!colour, Pawn);
+    let mut score: Score = Score::default();
+
+    if board.position.bb(colour, Bishop).count_ones() >= 2 {
+        score += BISHOP_PAIR;
+    }
+
+    let seventh: BB = RANK1 << (8 * relative(6, colour));
+    let eighth: BB = RANK1 << (8 * relative(7, colour));
+    let king: SquareIndex = board.position.bb(colour, King).trailing_zeros() as SquareIndex;
+    let safe: BB = safe_squares(board, colour);
+
+    for square in board.position.bb(colour, Rook).iter() {
+        let file: BB = FILEA << (square % 8);
+
+        if (ours | theirs) & file == 0 {
+            score += ROOK_OPEN_FILE;
+        } else if ours & file == 0 {
+            score += ROOK_SEMI_OPEN_FILE;
+        }
+
+        if square.to_bitboard() & seventh != 0
+            && (theirs & seventh != 0 || board.position.bb(!colour, King) & eighth != 0)
+        {
+            score += ROOK_ON_SEVENTH;
+        }
+
+        if (moves(lookup, board, Rook, square) & safe).count_ones() <= 3
+            && is_trapped_by_king(board, square, king, colour)
+        {
+            score += TRAPPED_ROOK;
+        }
+    }
+
+    // Squares that enemy pawns attack or could attack after advancing
+    let their_reach: BB = fill(attacks(theirs, -forward(colour)), -forward(colour));
+
+    for square in board.position.bb(colour, Knight).iter() {
+        if (3..=5).contains(&relative_rank(square, colour))
+            && square.to_bitboard() & attacks(ours, forward(colour)) & !their_reach != 0
+        {
+            score += KNIGHT_OUTPOST;
+        }
+    }
+
+    for (bishop, pawn) in BISHOP_TRAPS {
+        let (bishop, pawn): (SquareIndex, SquareIndex) = match colour {
+            White => (bishop as SquareIndex, pawn as SquareIndex),
+            Black => (
+                bishop as SquareIndex ^ 0b111_000,
+                pawn as SquareIndex ^ 0b111_000,
+            ),
+        };
+
+        if board.position.bb(colour, Bishop) & bishop.to_bitboard() != 0
+            && theirs & pawn.to_bitboard() != 0
+        {
+            score += TRAPPED_BISHOP;
+        }
+    }
+
+    score * CENTIPAWN
+}
+
+fn moves(lookup: &Lookup, board: &BoardState, piece: PieceType, square: SquareIndex) -> BB {
+    match piece {
+        Knight => lookup.moves(square, Knight),
+        _ => lookup.sliding_moves(square, board.position.bb_all(), piece),
+    }
+}
+
+fn safe_squares(board: &BoardState, colour: Colour) -> BB {
+    let their_pawns: BB = board.position.bb(!colour, Pawn);
+
+    !board.position.bb_colour(colour) & !attacks(their_pawns, -forward(colour))
+}
+
+/// The absolute rank of `colour`'s relative `rank`
+fn relative(rank: u64, colour: Colour) -> u64 {
+    match colour {
+        White => rank,
+        Black => 7 - rank,
+    }
+}
+
+/// Whether a rook on our first two ranks is stuck in the corner beside our king
+fn is_trapped_by_king(
+    board: &BoardState,
+    rook: SquareIndex,
+    king: SquareIndex,
+    colour: Colour,
+) -> bool {
+    let castling: [bool; 2] = match colour {
+        White => [board.castling_rights[0], board.castling_rights[1]],
+        Black => [board.castling_rights[2], board.castling_rights[3]],
+    };
+
+    let (rook_file, king_file): (u64, u64) = (rook % 8, king % 8);
+
+    relative_rank(king, colour) == 0
+        && relative_rank(rook, colour) <= 1
+        && !castling.contains(&true)
+        && if king_file >= 4 {
+            rook_file > king_file
+        } else {
+            rook_file < king_file
+        }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{fen, movegen::lookup::Lookup};
+
+    use super::{activity, mobility, Colour::*, Score, CENTIPAWN, KNIGHT_OUTPOST, TRAPPED_BISHOP};
+
+    #[test]
+    fn rewards_active_pieces() {
+        let lookup: Lookup = Lookup::default();
+
+        // A centralised knight beats one in the corner, unless pawns cover its squares
+        let centre = fen::parse("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1").unwrap();
+        let corner = fen::parse("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").unwrap();
+        let covered = fen::parse("4k3/8/2p1p3/8/3N4/8/8/4K3 w - - 0 1").unwrap();
+        assert!(mobility(&lookup, &centre, White).0 > mobility(&lookup, &corner, White).0);
+        assert!(mobility(&lookup, &centre, White).0 > mobility(&lookup, &covered, White).0);
+
+        // A defended knight on e5 that no pawn can chase away is an outpost, with d6 it is not
+        let outpost = fen::parse("4k3/p6p/8/4N3/3P4/8/8/4K3 w - - 0 1").unwrap();
+        let chased = fen::parse("4k3/p6p/3p4/4N3/3P4/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            activity(&lookup, &outpost, White) - activity(&lookup, &chased, White),
+            KNIGHT_OUTPOST * CENTIPAWN
+        );
+
+        // Rooks like open files and the 7th, and a black bishop taking on h2 is trapped by g3
+        let rooks = fen::parse("6k1/R4ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        assert!(activity(&lookup, &rooks, White).0 > 0);
+
+        let trapped = fen::parse("4k3/8/8/8/8/6P1/7b/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            activity(&lookup, &trapped, Black),
+            TRAPPED_BISHOP * CENTIPAWN
+        );
+
+        // A rook in the corner behind a king that has given up castling is trapped, unlike after castling
+        let stuck = fen::parse("4k3/8/8/8/8/8/5PPP/5KR1 w - - 0 1").unwrap();
+        let free = fen::parse("4k3/8/8/8/8/8/5PPP/5RK1 w - - 0 1").unwrap();
+        assert!(activity(&lookup, &stuck, White).0 < activity(&lookup, &free, White).0);
+        assert_eq!(Score::default(), activity(&lookup, &corner, White));
+    }
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use super::{eval::PAWN_VALUE, SearchResult, MATE, MATE_BOUND};
+use crate::types::chess_move::Move;
+
+/// Search statistics reported alongside each completed iteration
+pub struct IterationInfo<'a> {
+    pub result: &'a SearchResult,
+    pub seldepth: i32,
+    pub nodes: u64,
+    pub elapsed: Duration,
+    pub hashfull: usize,
+}
+
+impl std::fmt::Display for IterationInfo<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let millis: u128 = self.elapsed.as_millis();
+        let nps: u128 = u128::from(self.nodes) * 1000 / millis.max(1);
+
+        write!(
+            f,
+            "info depth {} seldepth {} score {} nodes {} nps {nps} time {millis} hashfull {} pv",
+            self.result.depth,
+            self.seldepth,
+            uci_score(self.result.score),
+            self.nodes,
+            self.hashfull,
+        )?;
+
+        for mv in &self.result.pv {
+            write!(f, " {mv}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Converts a score from internal millipawns to centipawns, or to moves until mate
+pub fn uci_score(score: i32) -> String {
+    if score >= MATE_BOUND {
+        format!("mate {}", (MATE - score + 1) / 2)
+    } else if score <= -MATE_BOUND {
+        format!("mate {}", -(MATE + score) / 2)
+    } else {
+        format!("cp {}", score * 100 / PAWN_VALUE as i32)
+    }
+}
+
+pub fn currmove(depth: i32, mv: Move, number: usize) -> String {
+    format!("info depth {depth} currmove {mv} currmovenumber {number}")
+}
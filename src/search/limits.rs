@@ -0,0 +1,18 @@
+use crate::types::chess_move::Move;
+
+/// Everything that can bound a search, as given by the parameters of the UCI `go` command
+#[derive(Debug, Clone, Default)]
+pub struct SearchLimits {
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u64>,
+    pub movetime: Option<u64>,
+    pub depth: Option<i32>,
+    pub nodes: Option<u64>,
+    pub mate: Option<i32>,
+    pub infinite: bool,
+    pub ponder: bool,
+    pub searchmoves: Vec<Move>,
+}
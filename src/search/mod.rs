@@ -1,17 +1,64 @@
 // pub mod eval;
 pub mod eval;
+pub mod info;
+pub mod limits;
+pub mod ordering;
 pub mod perft;
+pub mod pv;
+pub mod time;
+pub mod tt;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
 use crate::{
     movegen::MoveGen,
-    types::{board_state::BoardState, chess_move::Move, EngineError},
+    types::{
+        board_state::{BoardState, MoveStrategy},
+        chess_move::{Move, MoveType},
+        game::{Game, History, Status},
+        piece_type::PieceType,
+        EngineError,
+    },
+};
+
+use self::{
+    eval::{Evaluator, Trace},
+    info::IterationInfo,
+    limits::SearchLimits,
+    ordering::MoveOrderer,
+    pv::PvTable,
+    time::TimeManager,
+    tt::{Bound, TTEntry, TranspositionTable},
 };
 
+/// The outcome of the last completed iteration of a search
+#[derive(Debug, Clone, Default)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: i32,
+    pub pv: Vec<Move>,
+}
+
+/// Flags shared with the thread driving a search, which may be running on another thread
+#[derive(Debug, Clone, Default)]
+pub struct SearchSignals {
+    /// Set to abandon the search as soon as possible
+    pub stop: Arc<AtomicBool>,
+    /// Set while pondering, time limits only start to apply once it is cleared by `ponderhit`
+    pub ponder: Arc<AtomicBool>,
+}
+
 pub trait Searcher {
-    fn search(&mut self, board: BoardState, depth: i32)
-        -> Result<(Option<Move>, i32), EngineError>;
+    fn search(&mut self, game: &Game, limits: &SearchLimits) -> Result<SearchResult, EngineError>;
     fn inner(
         &mut self,
-        board: BoardState,
+        board: &mut BoardState,
         ply: i32,
         remaining: i32,
         alpha: i32,
@@ -19,45 +66,236 @@ pub trait Searcher {
     ) -> Result<i32, EngineError>;
 }
 
-pub struct NegaMax(MoveGen);
+#[derive(Default)]
+pub struct NegaMax {
+    gen: MoveGen,
+    tt: TranspositionTable,
+    evaluator: Evaluator,
+    strategy: MoveStrategy,
+    orderer: MoveOrderer,
+    pv: PvTable,
+    prev_pv: Vec<Move>,
+    /// The game so far followed by the current search path
+    history: History,
+    limits: SearchLimits,
+    timer: TimeManager,
+    signals: SearchSignals,
+    pondering: bool,
+    nodes: u64,
+    seldepth: i32,
+    stopped: bool,
+    info: bool,
+}
+
+/// Leeway given to captures in the quiescence search before they are delta pruned
+const DELTA_MARGIN: i32 = 2000;
+/// How many nodes are searched between each check of the clock
+const TIME_CHECK_INTERVAL: u64 = 2048;
+/// Root moves are only announced with `currmove` once the search has run this long
+const CURRMOVE_DELAY: Duration = Duration::from_secs(3);
 
-impl Default for NegaMax {
-    fn default() -> Self {
-        Self(MoveGen::default())
+impl NegaMax {
+    pub fn set_hash_size(&mut self, mb: usize) {
+        self.tt.resize(mb);
     }
-}
 
-const MATE: i32 = 100000;
+    /// Forget everything learnt from previous searches, e.g. on `ucinewgame`
+    pub fn clear(&mut self) {
+        self.tt.clear();
+        self.evaluator.clear();
+        self.orderer.clear();
+    }
 
-impl Searcher for NegaMax {
-    fn search(
+    /// Every term of the evaluation of `board`, from the evaluator the search uses
+    pub fn trace(&mut self, board: &BoardState) -> Trace {
+        self.evaluator.trace(board)
+    }
+
+    pub fn set_strategy(&mut self, strategy: MoveStrategy) {
+        self.strategy = strategy;
+    }
+
+    /// Whether UCI `info` lines are printed while searching
+    pub fn set_info(&mut self, info: bool) {
+        self.info = info;
+    }
+
+    pub fn signals(&self) -> SearchSignals {
+        self.signals.clone()
+    }
+
+    /// Time limits don't apply while pondering, and the clock restarts on `ponderhit`
+    fn clock_running(&mut self) -> bool {
+        let pondering: bool = self.signals.ponder.load(Ordering::Relaxed);
+
+        if self.pondering && !pondering {
+            self.timer.restart();
+        }
+
+        self.pondering = pondering;
+        !pondering
+    }
+
+    /// Polled at every node, flags the search to unwind once a node or time limit is hit
+    fn should_stop(&mut self) -> bool {
+        if self.stopped {
+            return true;
+        }
+
+        let stop_signalled: bool = self.signals.stop.load(Ordering::Relaxed);
+        let out_of_nodes: bool = self
+            .limits
+            .nodes
+            .is_some_and(|limit: u64| self.nodes >= limit);
+        let out_of_time: bool = self.nodes.is_multiple_of(TIME_CHECK_INTERVAL)
+            && self.clock_running()
+            && self.timer.hard_expired();
+
+        self.stopped = stop_signalled || out_of_nodes || out_of_time;
+        self.stopped
+    }
+
+    /// Searches captures and promotions until the position is quiet, so the static evaluation is
+    /// never taken in the middle of an exchange. In check, every evasion is searched instead.
+    fn quiescence(
+        &mut self,
+        board: &mut BoardState,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+    ) -> Result<i32, EngineError> {
+        self.pv.clear(ply);
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+
+        if ply >= MAX_PLY {
+            return Ok(self.evaluator.eval(board));
+        }
+
+        if self.should_stop() || board.is_insufficient_material() {
+            return Ok(0);
+        }
+
+        let in_check: bool = self.gen.is_check(board, board.active_player);
+
+        let (mut moves, stand_pat) = if in_check {
+            let moves: Vec<Move> = self.gen.all_moves(board)?;
+
+            if moves.is_empty() {
+                return Ok(self.no_moves_score(board, ply));
+            }
+
+            (moves, -MATE)
+        } else {
+            let stand_pat: i32 = self.evaluator.eval(board);
+
+            if stand_pat >= beta {
+                return Ok(stand_pat);
+            }
+
+            (self.gen.captures(board)?, stand_pat)
+        };
+
+        alpha = i32::max(alpha, stand_pat);
+
+        self.orderer.order(board, &mut moves, Move::NULL, ply);
+
+        let mut eval: i32 = stand_pat;
+
+        for mv in moves {
+            // Delta pruning: skip captures that can't raise alpha even with a generous margin
+            if !in_check
+                && !mv.is_promotion()
+                && stand_pat + Self::capture_value(board, &mv) + DELTA_MARGIN < alpha
+            {
+                continue;
+            }
+
+            let move_ev: i32 = -self.visit(board, &mv, |search: &mut Self, child| {
+                search.quiescence(child, ply + 1, -beta, -alpha)
+            })?;
+
+            if self.stopped {
+                return Ok(0);
+            }
+
+            eval = i32::max(eval, move_ev);
+            alpha = i32::max(alpha, eval);
+
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        Ok(eval)
+    }
+
+    /// Searches the position after `mv` with `f`, keeping the history of the search path up to date
+    fn visit(
+        &mut self,
+        board: &mut BoardState,
+        mv: &Move,
+        f: impl FnOnce(&mut Self, &mut BoardState) -> Result<i32, EngineError>,
+    ) -> Result<i32, EngineError> {
+        let strategy: MoveStrategy = self.strategy;
+
+        self.history.push(board.key);
+        let result: Result<i32, EngineError> =
+            strategy.with_move(board, mv, |child: &mut BoardState| f(self, child));
+        self.history.pop();
+
+        result
+    }
+
+    fn capture_value(board: &BoardState, mv: &Move) -> i32 {
+        if mv.kind == MoveType::EnPassantCapture {
+            return eval::piece_value(PieceType::Pawn);
+        }
+
+        board.position.type_at(mv.to).map_or(0, eval::piece_value)
+    }
+
+    fn search_root(
         &mut self,
-        board: BoardState,
+        mut board: BoardState,
         depth: i32,
-    ) -> Result<(Option<Move>, i32), EngineError> {
-        let mut best_mv: Move = Move::NULL;
+    ) -> Result<SearchResult, EngineError> {
         let mut best_ev: i32 = i32::MIN;
 
         let mut alpha: i32 = i32::MIN + 1;
         let beta: i32 = i32::MAX;
 
-        if depth <= 0 {
-            return Err(EngineError(String::from(
-                "[NegaMax::search()] Only non-zero depth values are allowed",
-            )));
+        self.pv.clear(0);
+
+        let mut moves: Vec<Move> = self.gen.all_moves(&board)?;
+
+        if !self.limits.searchmoves.is_empty() {
+            moves.retain(|mv: &Move| self.limits.searchmoves.contains(mv));
         }
 
-        let moves: Vec<Move> = self.0.all_moves(&board)?;
+        // A move to play even if the first iteration is stopped early
+        let mut best_mv: Move = moves.first().copied().unwrap_or(Move::NULL);
 
-        // childNodes := orderMoves(childNodes)
+        let pv_mv: Move = self.prev_pv.first().copied().unwrap_or(Move::NULL);
+        self.orderer.order(&board, &mut moves, pv_mv, 0);
 
-        for mv in &moves {
-            let applied: BoardState = board.clone_with_move(&mv)?;
-            let move_ev: i32 = -self.inner(applied, 1, depth - 1, -beta, -alpha)?;
+        for (i, mv) in moves.iter().enumerate() {
+            if self.info && self.timer.elapsed() >= CURRMOVE_DELAY {
+                println!("{}", info::currmove(depth, *mv, i + 1));
+            }
+
+            let move_ev: i32 = -self.visit(&mut board, mv, |search: &mut Self, child| {
+                search.inner(child, 1, depth - 1, -beta, -alpha)
+            })?;
+
+            if self.stopped {
+                break;
+            }
 
             if move_ev > best_ev {
                 best_ev = move_ev;
                 best_mv = *mv;
+                self.pv.update(0, *mv);
             }
 
             alpha = i32::max(alpha, best_ev);
@@ -67,49 +305,176 @@ impl Searcher for NegaMax {
             }
         }
 
-        if moves.len() == 0 {
-            return if self.0.is_check(&board, board.active_player) {
-                Ok((None, 0))
-            } else {
-                Ok((None, -MATE))
+        if moves.is_empty() {
+            return Ok(SearchResult {
+                best_move: None,
+                score: self.no_moves_score(&board, 0),
+                depth,
+                pv: vec![],
+            });
+        }
+
+        if !self.stopped {
+            self.tt
+                .store(board.key, depth, Bound::Exact, best_ev, best_mv, 0);
+        }
+
+        // Stopped before the first move was searched, the static evaluation is the best guess
+        if best_ev == i32::MIN {
+            best_ev = self.evaluator.eval(&board);
+        }
+
+        let mut pv: Vec<Move> = self.pv.line();
+        self.extend_pv(board, &mut pv, depth)?;
+
+        Ok(SearchResult {
+            best_move: Some(best_mv),
+            score: best_ev,
+            depth,
+            pv,
+        })
+    }
+
+    /// A repetition of any earlier position is scored as a draw straight away, as are dead positions
+    /// and positions where the fifty-move rule can be claimed unless it is checkmate
+    fn is_draw(&mut self, board: &BoardState) -> Result<bool, EngineError> {
+        if self.history.repetitions(board) > 0 || board.is_insufficient_material() {
+            return Ok(true);
+        }
+
+        Ok(board.half_moves >= 100 && board.status(&mut self.gen)?.is_draw())
+    }
+
+    /// The score of a position without legal moves, as `Status::from_moves` classifies it.
+    /// Checkmate is scored by its distance from the root, so shorter mates are preferred
+    fn no_moves_score(&self, board: &BoardState, ply: i32) -> i32 {
+        let in_check: bool = self.gen.is_check(board, board.active_player);
+
+        match Status::from_moves(board.active_player, true, in_check) {
+            Status::Checkmate(_) => -(MATE - ply),
+            _ => 0,
+        }
+    }
+
+    /// Lines cut short by transposition table hits are completed by following the stored best moves
+    fn extend_pv(
+        &mut self,
+        mut board: BoardState,
+        pv: &mut Vec<Move>,
+        depth: i32,
+    ) -> Result<(), EngineError> {
+        for mv in pv.iter() {
+            board.make_move(mv)?;
+        }
+
+        while (pv.len() as i32) < depth {
+            let Some(entry) = self.tt.probe(board.key) else {
+                break;
             };
+
+            if !self.gen.all_moves(&board)?.contains(&entry.best_move) {
+                break;
+            }
+
+            board.make_move(&entry.best_move)?;
+            pv.push(entry.best_move);
+        }
+
+        Ok(())
+    }
+}
+
+const MATE: i32 = 100000;
+const MAX_PLY: i32 = 128;
+/// Scores beyond this are mate scores
+const MATE_BOUND: i32 = MATE - MAX_PLY;
+
+impl Searcher for NegaMax {
+    /// Iterative deepening, each iteration is ordered by the principal variation of the last.
+    /// An iteration cut short by the limits is thrown away, except for the first.
+    fn search(&mut self, game: &Game, limits: &SearchLimits) -> Result<SearchResult, EngineError> {
+        let board: BoardState = game.board;
+
+        let max_depth: i32 = limits
+            .depth
+            .or(limits.mate.map(|mate: i32| 2 * mate))
+            .unwrap_or(MAX_PLY)
+            .min(MAX_PLY);
+
+        if max_depth <= 0 {
+            return Err(EngineError(String::from(
+                "[NegaMax::search()] Only non-zero depth values are allowed",
+            )));
+        }
+
+        self.limits = limits.clone();
+        self.timer = TimeManager::new(limits, board.active_player);
+        self.pondering = self.signals.ponder.load(Ordering::Relaxed);
+        self.nodes = 0;
+        self.seldepth = 0;
+        self.stopped = false;
+
+        self.history.clone_from(&game.history);
+        self.tt.new_search();
+        self.orderer.new_search();
+        self.prev_pv.clear();
+
+        let mut result: SearchResult = SearchResult::default();
+
+        for iteration in 1..=max_depth {
+            let iteration_result: SearchResult = self.search_root(board, iteration)?;
+
+            if self.stopped && iteration > 1 {
+                break;
+            }
+
+            result = iteration_result;
+            self.prev_pv.clone_from(&result.pv);
+
+            if self.info {
+                println!(
+                    "{}",
+                    IterationInfo {
+                        result: &result,
+                        seldepth: self.seldepth,
+                        nodes: self.nodes,
+                        elapsed: self.timer.elapsed(),
+                        hashfull: self.tt.hashfull(),
+                    }
+                );
+            }
+
+            if result.best_move.is_none()
+                || self.stopped
+                || (self.clock_running() && self.timer.soft_expired())
+            {
+                break;
+            }
+
+            if let Some(mate) = limits.mate {
+                if result.score >= MATE - (2 * mate - 1) {
+                    break;
+                }
+            }
         }
 
-        Ok((Some(best_mv), best_ev))
+        Ok(result)
     }
 
     fn inner(
         &mut self,
-        board: BoardState,
+        board: &mut BoardState,
         ply: i32,
         remaining: i32,
         mut alpha: i32,
         beta: i32,
     ) -> Result<i32, EngineError> {
-        // if remaining == 0 {
-        //     return quiescent(alpha, beta);
-        // }
+        // let moves: Vec<Move> = self.gen.all_moves(board)?;
 
-        // let moves: Vec<Move> = self.gen.all_moves(&board)?;
-
-        // let prevBestMove: Move = if plyFromRoot == 0 {
-        //     bestMove
-        // } else {
-        //     transpositionTable.TryGetStoredMove()
-        // };
-        // moveOrderer.OrderMoves(
-        //     prevBestMove,
-        //     board,
-        //     moves,
-        //     moveGenerator.opponentAttackMap,
-        //     moveGenerator.opponentPawnAttackMap,
-        //     false,
-        //     plyFromRoot,
-        // );
         // Detect checkmate and stalemate when no legal moves are available
 
         // if moves.len() == 0 {
-        //     return if self.gen.is_check(&board, board.active_player) {
+        //     return if self.gen.is_check(board, board.active_player) {
         //         Ok((Move::NULL, -(MATE - ply)))
         //     } else {
         //         Ok((Move::NULL, 0))
@@ -161,33 +526,6 @@ impl Searcher for NegaMax {
         // 	eval = -Search(plyRemaining - 1 + extension, plyFromRoot + 1, -beta, -alpha, numExtensions + extension, move, isCapture);
         // }
 
-        // Move was *too* good, opponent will choose a different move earlier on to avoid this position.
-        // (Beta-cutoff / Fail high)
-        // if eval >= beta
-        // {
-        // 	// Store evaluation in transposition table. Note that since we're exiting the search early, there may be an
-        // 	// even better move we haven't looked at yet, and so the current eval is a lower bound on the actual eval.
-        // 	transpositionTable.StoreEvaluation(plyRemaining, plyFromRoot, beta, TranspositionTable.LowerBound, moves[i]);
-
-        // 	// Update killer moves and history heuristic (note: don't include captures as theres are ranked highly anyway)
-        // 	if (!isCapture)
-        // 	{
-        // 		if ply < MoveOrdering.maxKillerMovePly
-        // 		{
-        // 			moveOrderer.killerMoves[plyFromRoot].Add(move);
-        // 		}
-        // 		int historyScore = plyRemaining * plyRemaining;
-        // 		moveOrderer.History[board.MoveColourIndex, moves[i].StartSquare, moves[i].TargetSquare] += historyScore;
-        // 	}
-        // 	if ply > 0
-        // 	{
-        // 		repetitionTable.TryPop();
-        // 	}
-
-        // 	searchDiagnostics.numCutOffs++;
-        // 	return beta;
-        // }
-
         // // Found a new best move in this position
         // if eval > alpha
         // {
@@ -207,36 +545,94 @@ impl Searcher for NegaMax {
         // todo!()
 
         if remaining == 0 {
-            return Ok(eval::eval(&board));
+            return self.quiescence(board, ply, alpha, beta);
         }
 
-        let moves: Vec<Move> = self.0.all_moves(&board)?;
+        self.pv.clear(ply);
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
 
-        if moves.len() == 0 {
-            return if self.0.is_check(&board, board.active_player) {
-                Ok(-MATE)
-            } else {
-                Ok(0)
-            };
+        if ply >= MAX_PLY {
+            return Ok(self.evaluator.eval(board));
+        }
+
+        if self.should_stop() {
+            return Ok(0);
+        }
+
+        if self.is_draw(board)? {
+            return Ok(0);
+        }
+
+        let original_alpha: i32 = alpha;
+        let entry: Option<TTEntry> = self.tt.probe(board.key);
+
+        if let Some(entry) = entry {
+            if entry.depth >= remaining {
+                let score: i32 = tt::score_from_tt(entry.score, ply);
+
+                match entry.bound {
+                    Bound::Exact => return Ok(score),
+                    Bound::Lower if score >= beta => return Ok(score),
+                    Bound::Upper if score <= alpha => return Ok(score),
+                    _ => {}
+                }
+            }
         }
 
-        // childNodes := orderMoves(childNodes)
+        let mut moves: Vec<Move> = self.gen.all_moves(board)?;
+
+        if moves.is_empty() {
+            return Ok(self.no_moves_score(board, ply));
+        }
+
+        // The hash move, or failing that the move from the last iteration's principal variation
+        let hash_mv: Move = entry
+            .map(|entry: TTEntry| entry.best_move)
+            .filter(|mv: &Move| *mv != Move::NULL)
+            .or_else(|| self.prev_pv.get(ply as usize).copied())
+            .unwrap_or(Move::NULL);
+        self.orderer.order(board, &mut moves, hash_mv, ply);
 
         let mut eval = i32::MIN;
+        let mut best_mv: Move = Move::NULL;
 
         for mv in moves {
-            let applied: BoardState = board.clone_with_move(&mv)?;
-            eval = i32::max(
-                eval,
-                -self.inner(applied, ply + 1, remaining - 1, -beta, -alpha)?,
-            );
+            let move_ev: i32 = -self.visit(board, &mv, |search: &mut Self, child| {
+                search.inner(child, ply + 1, remaining - 1, -beta, -alpha)
+            })?;
+
+            if self.stopped {
+                return Ok(0);
+            }
+
+            if move_ev > eval {
+                eval = move_ev;
+                best_mv = mv;
+            }
+
+            if move_ev > alpha {
+                self.pv.update(ply, mv);
+            }
 
             alpha = i32::max(alpha, eval);
             if alpha >= beta {
+                self.orderer.update_cutoff(board, mv, ply, remaining);
                 break;
             }
         }
 
+        let bound: Bound = if eval <= original_alpha {
+            Bound::Upper
+        } else if eval >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+
+        self.tt
+            .store(board.key, remaining, bound, eval, best_mv, ply);
+
         Ok(eval)
     }
 }
@@ -291,3 +687,37 @@ impl Searcher for NegaMax {
 //     /// Returns (relative eval, best move)
 //     pub fn search(&mut self) -> (i32, Move) {}
 // }
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::Ordering;
+
+    use crate::{fen, search::limits::SearchLimits, types::game::Game};
+
+    use super::{eval::PAWN_VALUE, NegaMax, SearchResult, Searcher, MATE};
+
+    #[test]
+    fn stopped_before_the_first_root_move() {
+        let mut search: NegaMax = NegaMax::default();
+        search.signals().stop.store(true, Ordering::Relaxed);
+
+        let game: Game = Game::new(fen::parse(fen::START).unwrap());
+        let result: SearchResult = search.search(&game, &SearchLimits::default()).unwrap();
+
+        // Still a move to play, scored by the static evaluation rather than left at i32::MIN
+        assert_eq!(result.depth, 1);
+        assert!(result.best_move.is_some());
+        assert!(result.score.abs() < PAWN_VALUE as i32, "{}", result.score);
+    }
+
+    #[test]
+    fn scores_positions_without_moves() {
+        let search: NegaMax = NegaMax::default();
+
+        // Checkmated by the back rank, then stalemated in the corner
+        let mate = fen::parse("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        let stalemate = fen::parse("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(search.no_moves_score(&mate, 3), -(MATE - 3));
+        assert_eq!(search.no_moves_score(&stalemate, 3), 0);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::types::{
+    board_state::BoardState,
+    chess_move::{Move, MoveType},
+    piece_type::PieceType::{self, *},
+};
+
+use super::MAX_PLY;
+
+const HASH_MOVE: i32 = 1 << 30;
+const GOOD_TACTICAL: i32 = 1 << 28;
+const FIRST_KILLER: i32 = 1 << 27;
+const SECOND_KILLER: i32 = FIRST_KILLER - 1;
+/// History scores are kept below the killers
+const MAX_HISTORY: i32 = 1 << 26;
+
+const KILLER_SLOTS: usize = 2;
+const PLIES: usize = MAX_PLY as usize + 1;
+
+/// Scores moves so the ones most likely to cause a cutoff are searched first: the hash move, then
+/// captures (most valuable victim, least valuable attacker) and promotions, then the killer moves of
+/// the ply, then the remaining quiet moves by their history
+pub struct MoveOrderer {
+    killers: [[Move; KILLER_SLOTS]; PLIES],
+    /// Butterfly table, indexed by colour, from and to square
+    history: Vec<[[i32; 64]; 64]>,
+}
+
+impl Default for MoveOrderer {
+    fn default() -> Self {
+        Self {
+            killers: [[Move::NULL; KILLER_SLOTS]; PLIES],
+            history: vec![[[0; 64]; 64]; 2],
+        }
+    }
+}
+
+impl MoveOrderer {
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Killers only make sense within one search, history is kept but decayed
+    pub fn new_search(&mut self) {
+        self.killers = [[Move::NULL; KILLER_SLOTS]; PLIES];
+
+        for score in self.history.iter_mut().flatten().flatten() {
+            *score /= 2;
+        }
+    }
+
+    /// Sorts `moves` best first
+    pub fn order(&self, board: &BoardState, moves: &mut [Move], hash_move: Move, ply: i32) {
+        moves.sort_by_cached_key(|mv: &Move| -self.score(board, mv, hash_move, ply));
+    }
+
+    fn score(&self, board: &BoardState, mv: &Move, hash_move: Move, ply: i32) -> i32 {
+        if *mv == hash_move {
+            return HASH_MOVE;
+        }
+
+        if is_tactical(board, mv) {
+            return GOOD_TACTICAL + mvv_lva(board, mv);
+        }
+
+        let killers: &[Move; KILLER_SLOTS] = &self.killers[ply as usize];
+
+        if *mv == killers[0] {
+            FIRST_KILLER
+        } else if *mv == killers[1] {
+            SECOND_KILLER
+        } else {
+            self.history[board.active_player as usize][mv.from as usize][mv.to as usize]
+        }
+    }
+
+    /// Remembers a quiet move that caused a beta cutoff, deeper cutoffs count for more
+    pub fn update_cutoff(&mut self, board: &BoardState, mv: Move, ply: i32, remaining: i32) {
+        if is_tactical(board, &mv) {
+            return;
+        }
+
+        let killers: &mut [Move; KILLER_SLOTS] = &mut self.killers[ply as usize];
+
+        if killers[0] != mv {
+            killers[1] = killers[0];
+            killers[0] = mv;
+        }
+
+        let score: &mut i32 =
+            &mut self.history[board.active_player as usize][mv.from as usize][mv.to as usize];
+        *score += remaining * remaining;
+
+        if *score >= MAX_HISTORY {
+            for score in self.history.iter_mut().flatten().flatten() {
+                *score /= 2;
+            }
+        }
+    }
+}
+
+fn is_tactical(board: &BoardState, mv: &Move) -> bool {
+    mv.is_promotion()
+        || mv.kind == MoveType::EnPassantCapture
+        || board.position.type_at(mv.to).is_some()
+}
+
+/// Orders captures by the victim first and the attacker second, promotions by the new piece
+pub fn mvv_lva(board: &BoardState, mv: &Move) -> i32 {
+    let victim: Option<PieceType> = if mv.kind == MoveType::EnPassantCapture {
+        Some(Pawn)
+    } else {
+        board.position.type_at(mv.to)
+    };
+    let attacker: Option<PieceType> = board.position.type_at(mv.from);
+
+    let capture: i32 = victim.map_or(0, |victim: PieceType| {
+        rank(victim) * 8 - attacker.map_or(0, rank)
+    });
+
+    capture
+        + mv.promoted_piece()
+            .map_or(0, |piece: PieceType| rank(piece) * 8)
+}
+
+fn rank(piece: PieceType) -> i32 {
+    match piece {
+        Pawn => 1,
+        Knight => 2,
+        Bishop => 3,
+        Rook => 4,
+        Queen => 5,
+        King => 6,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        fen,
+        movegen::MoveGen,
+        types::{board_state::BoardState, chess_move::Move},
+        uci::parse_move,
+    };
+
+    use super::MoveOrderer;
+
+    #[test]
+    fn orders_hash_captures_killers_then_quiets() {
+        let mut gen: MoveGen = MoveGen::default();
+        let mut orderer: MoveOrderer = MoveOrderer::default();
+        // White can take the queen with a pawn or the rook, or the knight with the queen
+        let board: BoardState = fen::parse("4k3/8/2n5/1q6/P7/8/1R4Q1/4K3 w - - 0 1").unwrap();
+        let mut mv = |notation: &str| -> Move { parse_move(&board, &mut gen, notation).unwrap() };
+
+        let hash: Move = mv("e1d1");
+        let killer: Move = mv("g2g8");
+        let pawn_takes_queen: Move = mv("a4b5");
+        let rook_takes_queen: Move = mv("b2b5");
+        let queen_takes_knight: Move = mv("g2c6");
+
+        orderer.update_cutoff(&board, killer, 1, 3);
+
+        let mut moves: Vec<Move> = gen.all_moves(&board).unwrap();
+        orderer.order(&board, &mut moves, hash, 1);
+
+        assert_eq!(
+            moves[..5],
+            [
+                hash,
+                pawn_takes_queen,
+                rook_takes_queen,
+                queen_takes_knight,
+                killer
+            ],
+            "{}",
+            moves
+                .iter()
+                .map(Move::to_string)
+                .collect::<Vec<String>>()
+                .join(" ")
+        );
+    }
+}
@@ -1,63 +1,347 @@
-// use std::time::Instant;
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    io::{BufRead, BufReader, Write},
+    mem::size_of,
+    ops::AddAssign,
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Instant,
+};
 
 use crate::{
+    fen,
     movegen::MoveGen,
     types::{
-        board_state::BoardState,
-        chess_move::{Move, MoveType::*},
-        square::{Square::*, SquareIndex},
+        bitboard::{ToBitboard, BB},
+        board_state::{BoardState, MoveStrategy},
+        chess_move::{Move, MoveType},
+        position::Position,
+        zobrist::ZobristKey,
         EngineError,
     },
 };
 
+use super::tt::table_len;
+
 #[derive(Default)]
 pub struct Perft {
     gen: MoveGen,
-    pub count: usize,
+    pub strategy: MoveStrategy,
+    /// Root moves are shared out between this many threads, one if unset
+    pub threads: usize,
+    table: Option<Arc<PerftTable>>,
+}
+
+/// Subtree node counts by Zobrist key and depth, shared by the perft threads without locking.
+/// Each entry holds the count and depth in one word and the key xor'd with that word in the other,
+/// so an entry torn by two threads writing at once reads as a miss.
+pub struct PerftTable {
+    entries: Vec<[AtomicU64; 2]>,
+    mask: usize,
+}
+
+/// Node counts below each move in UCI notation, as another engine's divide prints them
+type Divide = BTreeMap<String, usize>;
+
+/// Where `Perft::bisect` gets the correct divide of each position it reaches
+pub trait Reference {
+    /// The divide of `board` at `depth`, `None` if the reference doesn't have one
+    fn divide(&mut self, board: &BoardState, depth: i32) -> Result<Option<Divide>, EngineError>;
+}
+
+/// Divides pasted from another engine. A reference file holds one or more divides of `e2e4: N`
+/// lines, each after a `fen <FEN>` line naming its position. The first may leave the position out,
+/// it then belongs to the position being checked.
+#[derive(Debug, Default)]
+pub struct DivideReference {
+    divides: Vec<(BoardState, Divide)>,
+}
+
+/// Another engine run as a child process, which is asked for each divide with `position fen` and
+/// `go perft`, as Stockfish answers them
+pub struct EngineReference {
+    process: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+}
+
+/// What the moves into the leaf positions were and what they did, as in the perft results tables
+/// of the Chess Programming Wiki
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PerftStats {
+    pub nodes: usize,
+    /// Including en passant captures
+    pub captures: usize,
+    pub en_passant: usize,
+    pub castles: usize,
+    pub promotions: usize,
+    pub checks: usize,
+    /// Checks by a piece other than the one that moved, double checks are counted on their own
+    pub discovered_checks: usize,
+    pub double_checks: usize,
+    pub checkmates: usize,
+}
+
+/// Where our divide first disagrees with the reference
+#[derive(Debug)]
+pub struct Discrepancy {
+    /// The moves from the root to the position that differs
+    pub path: Vec<Move>,
+    pub board: BoardState,
+    pub depth: i32,
+    /// Moves only the reference has
+    pub missing: Vec<String>,
+    /// Moves only we have
+    pub extra: Vec<String>,
 }
 
 impl Perft {
+    /// Prints the node count below each root move, in the `e2e4: N` format of other engines
     pub fn verbose(&mut self, board: &BoardState, depth: i32) -> Result<usize, EngineError> {
+        let start: Instant = Instant::now();
+        let divide: Vec<(Move, usize)> = self.divide(board, depth)?;
+
+        for (mv, subtotal) in &divide {
+            println!("{mv}: {subtotal}");
+        }
+
+        let sum: usize = divide.iter().map(|(_, subtotal)| subtotal).sum();
+
+        println!("\nNodes searched: {sum}");
+        println!("Moves searched: {}", divide.len());
+
+        let millis: u128 = start.elapsed().as_millis();
+        let nps: u128 = sum as u128 * 1000 / millis.max(1);
+        println!(
+            "Time: {millis}ms ({}, {} threads, {}MB hash, {nps} nps)",
+            self.strategy,
+            self.threads.max(1),
+            self.table.as_ref().map_or(0, |table| table.size_mb())
+        );
+
+        Ok(sum)
+    }
+
+    /// Caches subtree counts in a table of `mb` megabytes, none if zero
+    pub fn set_hash_size(&mut self, mb: usize) {
+        self.table = (mb > 0).then(|| Arc::new(PerftTable::new(mb)));
+    }
+
+    /// The node count below each legal move, in generation order
+    pub fn divide(
+        &mut self,
+        board: &BoardState,
+        depth: i32,
+    ) -> Result<Vec<(Move, usize)>, EngineError> {
         let moves: Vec<Move> = self.gen.all_moves(board)?;
+        let threads: usize = self.threads.clamp(1, moves.len().max(1));
 
-        // if depth <= 1 {
-        //     println!("Possible moves: {}", moves.len());
-        //     return moves.len();
-        // }
+        if threads == 1 {
+            return moves
+                .into_iter()
+                .map(|mv: Move| Ok((mv, self.subtotal(board, &mv, depth)?)))
+                .collect();
+        }
 
-        let mut sum: usize = 0;
+        // Each thread takes the next unclaimed root move until none are left
+        let next: AtomicUsize = AtomicUsize::new(0);
+        let mut subtotals: Vec<(usize, usize)> = Vec::with_capacity(moves.len());
 
-        let temp: Move = Move {
-            from: G8 as SquareIndex,
-            to: H6 as SquareIndex,
-            kind: Normal,
-        };
+        thread::scope(|scope| -> Result<(), EngineError> {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    let mut worker: Perft = self.worker();
+                    let (next, moves) = (&next, &moves);
 
-        let mut trace = false;
+                    scope.spawn(move || -> Result<Vec<(usize, usize)>, EngineError> {
+                        let mut done: Vec<(usize, usize)> = Vec::new();
 
-        for mv in &moves {
-            let new_board: BoardState = board.clone_with_move(mv)?;
+                        loop {
+                            let index: usize = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(mv) = moves.get(index) else {
+                                return Ok(done);
+                            };
+                            done.push((index, worker.subtotal(board, mv, depth)?));
+                        }
+                    })
+                })
+                .collect();
 
-            if *mv == temp {
-                println!("HIT ↓");
-                trace = true;
+            for worker in workers {
+                subtotals.extend(worker.join().expect("perft thread panicked")?);
             }
 
-            let subtotal: usize = self.perft_inner(&new_board, depth - 1, trace)?;
+            Ok(())
+        })?;
+
+        subtotals.sort_unstable();
 
-            println!("{}: {}", mv, subtotal);
+        Ok(subtotals
+            .into_iter()
+            .map(|(index, subtotal)| (moves[index], subtotal))
+            .collect())
+    }
+
+    /// Counts the leaf nodes `depth` plies below `board` without printing anything
+    pub fn nodes(&mut self, board: &BoardState, depth: i32) -> Result<usize, EngineError> {
+        if depth <= 0 {
+            return Ok(1);
+        }
 
-            trace = false;
+        Ok(self
+            .divide(board, depth)?
+            .iter()
+            .map(|(_, subtotal)| subtotal)
+            .sum())
+    }
 
-            sum += subtotal;
+    /// A single-threaded perft for one of the threads of `divide`, sharing the hash table
+    fn worker(&self) -> Perft {
+        Perft {
+            gen: MoveGen::default(),
+            strategy: self.strategy,
+            threads: 1,
+            table: self.table.clone(),
         }
+    }
+
+    fn subtotal(
+        &mut self,
+        board: &BoardState,
+        mv: &Move,
+        depth: i32,
+    ) -> Result<usize, EngineError> {
+        let mut board: BoardState = *board;
 
-        // drop(temp);
+        self.strategy.with_move(&mut board, mv, |new_board| {
+            Self::debug_check_key(new_board, mv);
+            self.perft_inner(new_board, depth - 1)
+        })
+    }
 
-        println!("\nNodes searched: {}", sum);
-        println!("Moves searched: {}", moves.len());
+    /// Follows the first move whose count disagrees with the reference down the tree, asking the
+    /// reference for the divide of each position on the way, until the move lists themselves
+    /// differ. An engine reference can always answer, a file may run out of divides first.
+    /// Returns `None` if the counts agree.
+    pub fn bisect(
+        &mut self,
+        board: &BoardState,
+        depth: i32,
+        reference: &mut impl Reference,
+    ) -> Result<Option<Discrepancy>, EngineError> {
+        let mut board: BoardState = *board;
+        let mut depth: i32 = depth;
+        let mut path: Vec<Move> = Vec::new();
 
-        Ok(sum)
+        while depth > 0 {
+            let Some(expected) = reference.divide(&board, depth)? else {
+                return Ok(Some(Discrepancy {
+                    path,
+                    board,
+                    depth,
+                    missing: Vec::new(),
+                    extra: Vec::new(),
+                }));
+            };
+
+            let divide: Vec<(Move, usize)> = self.divide(&board, depth)?;
+            let ours: Divide = divide
+                .iter()
+                .map(|(mv, subtotal)| (mv.to_string(), *subtotal))
+                .collect();
+
+            let missing: Vec<String> = expected
+                .keys()
+                .filter(|mv: &&String| !ours.contains_key(*mv))
+                .cloned()
+                .collect();
+            let extra: Vec<String> = ours
+                .keys()
+                .filter(|mv: &&String| !expected.contains_key(*mv))
+                .cloned()
+                .collect();
+
+            if !missing.is_empty() || !extra.is_empty() {
+                return Ok(Some(Discrepancy {
+                    path,
+                    board,
+                    depth,
+                    missing,
+                    extra,
+                }));
+            }
+
+            let Some((mv, _)) = divide
+                .iter()
+                .find(|(mv, subtotal)| expected.get(&mv.to_string()) != Some(subtotal))
+            else {
+                return Ok(None);
+            };
+
+            board.make_move(mv)?;
+            path.push(*mv);
+            depth -= 1;
+        }
+
+        Ok(None)
+    }
+
+    /// Counts the leaf nodes `depth` plies below `board` by how they were reached
+    pub fn stats(&mut self, board: &BoardState, depth: i32) -> Result<PerftStats, EngineError> {
+        let mut board: BoardState = *board;
+        self.stats_inner(&mut board, depth)
+    }
+
+    fn stats_inner(
+        &mut self,
+        board: &mut BoardState,
+        depth: i32,
+    ) -> Result<PerftStats, EngineError> {
+        let mut stats: PerftStats = PerftStats::default();
+
+        if depth <= 0 {
+            stats.nodes = 1;
+            return Ok(stats);
+        }
+
+        for mv in &self.gen.all_moves(board)? {
+            let capture: bool = mv.is_capture(board);
+            let moved: BB = match Position::castle_squares(mv.kind, board.active_player) {
+                Some((_, king_to, _, rook_to)) => king_to.to_bitboard() | rook_to.to_bitboard(),
+                None => mv.to.to_bitboard(),
+            };
+
+            stats += self.strategy.with_move(board, mv, |new_board| {
+                if depth > 1 {
+                    return self.stats_inner(new_board, depth - 1);
+                }
+
+                let checkers: BB = self.gen.checkers(new_board);
+
+                Ok(PerftStats {
+                    nodes: 1,
+                    captures: usize::from(capture),
+                    en_passant: usize::from(mv.kind == MoveType::EnPassantCapture),
+                    castles: usize::from(mv.is_castle()),
+                    promotions: usize::from(mv.is_promotion()),
+                    checks: usize::from(checkers != 0),
+                    discovered_checks: usize::from(
+                        checkers.is_power_of_two() && checkers & !moved != 0,
+                    ),
+                    double_checks: usize::from(checkers.count_ones() > 1),
+                    checkmates: usize::from(
+                        checkers != 0 && self.gen.all_moves(new_board)?.is_empty(),
+                    ),
+                })
+            })?;
+        }
+
+        Ok(stats)
     }
 
     // pub fn perft(&mut self, depth: i32) {
@@ -68,33 +352,469 @@ impl Perft {
     //     println!("Nodes: {}", self.count);
     // }
 
-    fn perft_inner(
-        &mut self,
-        board: &BoardState,
-        depth: i32,
-        trace: bool,
-    ) -> Result<usize, EngineError> {
-        let moves: Vec<Move>;
-
-        if trace {
-            moves = self.gen.trace_generate(board)?;
-        } else {
-            moves = self.gen.all_moves(board)?;
+    fn perft_inner(&mut self, board: &mut BoardState, depth: i32) -> Result<usize, EngineError> {
+        if depth <= 0 {
+            return Ok(1);
         }
 
-        if depth <= 1 {
-            return Ok(moves.len());
+        if let Some(nodes) = self
+            .table
+            .as_ref()
+            .and_then(|table| table.probe(board.key, depth))
+        {
+            return Ok(nodes);
         }
 
+        let moves: Vec<Move> = self.gen.all_moves(board)?;
         let mut sum: usize = 0;
-        for mv in &moves {
-            let new_board: BoardState = board.clone_with_move(mv)?;
-            sum += self.perft_inner(&new_board, depth - 1, trace)?;
+
+        if depth == 1 {
+            sum = moves.len();
+        } else {
+            for mv in &moves {
+                sum += self.strategy.with_move(board, mv, |new_board| {
+                    Self::debug_check_key(new_board, mv);
+                    self.perft_inner(new_board, depth - 1)
+                })?;
+            }
+        }
+
+        if let Some(table) = &self.table {
+            table.store(board.key, depth, sum);
         }
+
         Ok(sum)
     }
 
-    pub fn reset(&mut self) {
-        self.count = 0;
+    /// In debug builds, checks the incrementally updated Zobrist keys against a full recomputation
+    fn debug_check_key(board: &BoardState, mv: &Move) {
+        debug_assert_eq!(
+            board.key,
+            board.compute_key(),
+            "Zobrist key desync after {mv}\n{board}"
+        );
+        debug_assert_eq!(
+            board.pawn_key,
+            board.compute_pawn_key(),
+            "Pawn key desync after {mv}\n{board}"
+        );
+    }
+}
+
+impl PerftTable {
+    /// Allocates a table of `mb` megabytes, at least one
+    pub fn new(mb: usize) -> Self {
+        let len: usize = table_len::<[AtomicU64; 2]>(mb.max(1));
+
+        Self {
+            entries: (0..len)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+            mask: len - 1,
+        }
+    }
+
+    pub fn size_mb(&self) -> usize {
+        self.entries.len() * size_of::<[AtomicU64; 2]>() / (1024 * 1024)
+    }
+
+    fn probe(&self, key: ZobristKey, depth: i32) -> Option<usize> {
+        let [check, data] = &self.entries[key as usize & self.mask];
+        let data: u64 = data.load(Ordering::Relaxed);
+
+        (check.load(Ordering::Relaxed) ^ data == key && data & 0xFF == depth as u64)
+            .then_some((data >> 8) as usize)
+    }
+
+    /// Always replaces, deeper counts are no more valuable to keep than recent ones here
+    fn store(&self, key: ZobristKey, depth: i32, nodes: usize) {
+        let [check, data] = &self.entries[key as usize & self.mask];
+        let word: u64 = (nodes as u64) << 8 | depth as u64;
+
+        check.store(key ^ word, Ordering::Relaxed);
+        data.store(word, Ordering::Relaxed);
+    }
+}
+
+impl DivideReference {
+    /// Reads `e2e4: N` lines, e.g. pasted from another engine's `go perft`, for `root` and the
+    /// positions named in the text. Other lines, such as totals, are skipped.
+    pub fn parse(text: &str, root: &BoardState) -> Result<Self, EngineError> {
+        let mut reference: Self = Self::default();
+
+        for line in text.lines().map(str::trim) {
+            if let Some(notation) = line
+                .strip_prefix("position fen ")
+                .or_else(|| line.strip_prefix("fen "))
+            {
+                reference
+                    .divides
+                    .push((fen::parse(notation)?, Divide::new()));
+                continue;
+            }
+
+            let Some((mv, nodes)) = divide_line(line)? else {
+                continue;
+            };
+
+            if reference.divides.is_empty() {
+                reference.divides.push((*root, Divide::new()));
+            }
+
+            if let Some((_, divide)) = reference.divides.last_mut() {
+                divide.insert(mv, nodes);
+            }
+        }
+
+        if reference.divides.is_empty() {
+            return Err(EngineError(String::from(
+                "[DivideReference::parse()] No divide lines found",
+            )));
+        }
+
+        Ok(reference)
+    }
+}
+
+impl Reference for DivideReference {
+    fn divide(&mut self, board: &BoardState, _: i32) -> Result<Option<Divide>, EngineError> {
+        Ok(self
+            .divides
+            .iter()
+            .find(|(position, _)| position.key == board.key)
+            .map(|(_, divide)| divide.clone()))
+    }
+}
+
+impl EngineReference {
+    /// Starts the engine at `path` and waits for it to answer `uci`
+    pub fn spawn(path: &str) -> Result<Self, EngineError> {
+        let error =
+            |e: std::io::Error| EngineError(format!("[EngineReference::spawn()] '{path}': {e}"));
+
+        let mut process: Child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(error)?;
+        let (Some(input), Some(output)) = (process.stdin.take(), process.stdout.take()) else {
+            return Err(EngineError(format!(
+                "[EngineReference::spawn()] No pipes to '{path}'"
+            )));
+        };
+
+        let mut reference: Self = Self {
+            process,
+            input,
+            output: BufReader::new(output),
+        };
+        reference.send("uci")?;
+
+        while reference.read_line()? != "uciok" {}
+
+        Ok(reference)
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), EngineError> {
+        writeln!(self.input, "{command}")
+            .and_then(|()| self.input.flush())
+            .map_err(|e| EngineError(format!("[EngineReference::send()] {e}")))
+    }
+
+    fn read_line(&mut self) -> Result<String, EngineError> {
+        let mut line: String = String::new();
+
+        match self.output.read_line(&mut line) {
+            Ok(0) => Err(EngineError(String::from(
+                "[EngineReference::read_line()] The engine exited",
+            ))),
+            Ok(_) => Ok(String::from(line.trim())),
+            Err(e) => Err(EngineError(format!("[EngineReference::read_line()] {e}"))),
+        }
+    }
+}
+
+impl Reference for EngineReference {
+    /// Reads `e2e4: N` lines up to the engine's `Nodes searched` total
+    fn divide(&mut self, board: &BoardState, depth: i32) -> Result<Option<Divide>, EngineError> {
+        self.send(&format!("position fen {}", fen::board_to_fen(board)?))?;
+        self.send(&format!("go perft {depth}"))?;
+
+        let mut divide: Divide = Divide::new();
+
+        loop {
+            let line: String = self.read_line()?;
+
+            if line.starts_with("Nodes searched") {
+                return Ok(Some(divide));
+            }
+
+            if let Some((mv, nodes)) = divide_line(&line)? {
+                divide.insert(mv, nodes);
+            }
+        }
+    }
+}
+
+impl Drop for EngineReference {
+    fn drop(&mut self) {
+        // The engine may already be gone, there is nothing more to do about it then
+        let _ = self.send("quit");
+        let _ = self.process.wait();
+    }
+}
+
+impl AddAssign for PerftStats {
+    fn add_assign(&mut self, other: Self) {
+        self.nodes += other.nodes;
+        self.captures += other.captures;
+        self.en_passant += other.en_passant;
+        self.castles += other.castles;
+        self.promotions += other.promotions;
+        self.checks += other.checks;
+        self.discovered_checks += other.discovered_checks;
+        self.double_checks += other.double_checks;
+        self.checkmates += other.checkmates;
+    }
+}
+
+impl PerftStats {
+    pub const HEADER: &'static str =
+        "Depth       Nodes    Captures      E.p.   Castles  Promotions     Checks  Discovery  Double  Mates";
+}
+
+impl Display for PerftStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:>11} {:>11} {:>9} {:>9} {:>11} {:>10} {:>10} {:>7} {:>6}",
+            self.nodes,
+            self.captures,
+            self.en_passant,
+            self.castles,
+            self.promotions,
+            self.checks,
+            self.discovered_checks,
+            self.double_checks,
+            self.checkmates
+        )
+    }
+}
+
+impl Display for Discrepancy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fen: String = fen::board_to_fen(&self.board).map_err(|_| std::fmt::Error)?;
+        let path: String = self
+            .path
+            .iter()
+            .map(Move::to_string)
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        if self.missing.is_empty() && self.extra.is_empty() {
+            return write!(
+                f,
+                "Counts differ after '{path}', the reference has no divide at depth {} for:\nfen {fen}",
+                self.depth
+            );
+        }
+
+        write!(
+            f,
+            "Move lists differ after '{path}' at\nfen {fen}\nMissing: {}\nExtra: {}",
+            self.missing.join(" "),
+            self.extra.join(" ")
+        )
+    }
+}
+
+/// The move and count of an `e2e4: N` line, `None` for any other line
+fn divide_line(line: &str) -> Result<Option<(String, usize)>, EngineError> {
+    let Some((mv, nodes)) = line.split_once(':') else {
+        return Ok(None);
+    };
+
+    if !is_uci_move(mv.trim()) {
+        return Ok(None);
+    }
+
+    Ok(Some((String::from(mv.trim()), nodes.trim().parse()?)))
+}
+
+/// Whether `notation` looks like a move in UCI notation, e.g. `e2e4` or `e7e8q`
+fn is_uci_move(notation: &str) -> bool {
+    let chars: Vec<char> = notation.chars().collect();
+
+    (chars.len() == 4 || chars.len() == 5)
+        && chars
+            .chunks(2)
+            .take(2)
+            .all(|square: &[char]| matches!(square, ['a'..='h', '1'..='8']))
+        && chars[4..]
+            .iter()
+            .all(|piece: &char| "nbrq".contains(*piece))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        fen,
+        movegen::MoveGen,
+        types::{board_state::BoardState, EngineError},
+    };
+
+    use super::{Divide, DivideReference, Perft, PerftStats, Reference};
+
+    /// A reference engine that finds one more reply, h1h3, than we do in `bugged`
+    struct Engine {
+        gen: MoveGen,
+        bugged: Option<BoardState>,
+    }
+
+    impl Reference for Engine {
+        fn divide(
+            &mut self,
+            board: &BoardState,
+            depth: i32,
+        ) -> Result<Option<Divide>, EngineError> {
+            let mut divide: Divide = Divide::new();
+
+            for mv in self.gen.all_moves(board)? {
+                let nodes: usize = match depth {
+                    1 => 1,
+                    _ => self
+                        .divide(&board.clone_with_move(&mv)?, depth - 1)?
+                        .map_or(0, |divide: Divide| divide.values().sum()),
+                };
+                divide.insert(mv.to_string(), nodes);
+            }
+
+            if depth == 1 && self.bugged.is_some_and(|bugged| bugged.key == board.key) {
+                divide.insert(String::from("h1h3"), 1);
+            }
+
+            Ok(Some(divide))
+        }
+    }
+
+    fn to_text(divide: &Divide) -> String {
+        divide
+            .iter()
+            .map(|(mv, nodes)| format!("{mv}: {nodes}\n"))
+            .collect::<Vec<String>>()
+            .concat()
+    }
+
+    #[test]
+    fn bisects_to_the_differing_position() {
+        let mut perft: Perft = Perft::default();
+        let board: BoardState = fen::parse(fen::START).unwrap();
+        let king_pawn: BoardState =
+            fen::parse("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+        let scandinavian: BoardState =
+            fen::parse("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2").unwrap();
+
+        let mut engine: Engine = Engine {
+            gen: MoveGen::default(),
+            bugged: None,
+        };
+        assert!(perft.bisect(&board, 3, &mut engine).unwrap().is_none());
+
+        // The engine alone leads bisect down e2e4 d7d5 to the reply we don't have
+        engine.bugged = Some(scandinavian);
+        let discrepancy = perft.bisect(&board, 3, &mut engine).unwrap().unwrap();
+        assert_eq!(discrepancy.path.len(), 2);
+        assert_eq!(discrepancy.board.key, scandinavian.key);
+        assert_eq!(discrepancy.missing, ["h1h3"]);
+        assert!(discrepancy.extra.is_empty());
+
+        // A file with only the root divide can't say which reply to e2e4 is wrong
+        let root: String = to_text(&engine.divide(&board, 3).unwrap().unwrap());
+        let mut reference: DivideReference =
+            DivideReference::parse(&format!("{root}\nNodes searched: 8903\n"), &board).unwrap();
+        let discrepancy = perft.bisect(&board, 3, &mut reference).unwrap().unwrap();
+        assert_eq!(discrepancy.board.key, king_pawn.key);
+        assert!(discrepancy.missing.is_empty() && discrepancy.extra.is_empty());
+
+        // Unless it goes on with the divides below, each after its position
+        let e4: String = to_text(&engine.divide(&king_pawn, 2).unwrap().unwrap());
+        let d5: String = to_text(&engine.divide(&scandinavian, 1).unwrap().unwrap());
+        let mut reference: DivideReference = DivideReference::parse(
+            &format!(
+                "{root}\nfen {}\n{e4}\nposition fen {}\n{d5}",
+                fen::board_to_fen(&king_pawn).unwrap(),
+                fen::board_to_fen(&scandinavian).unwrap()
+            ),
+            &board,
+        )
+        .unwrap();
+        let discrepancy = perft.bisect(&board, 3, &mut reference).unwrap().unwrap();
+        assert_eq!(discrepancy.board.key, scandinavian.key);
+        assert_eq!(discrepancy.missing, ["h1h3"]);
+    }
+
+    #[test]
+    fn counts_leaf_statistics() {
+        let mut perft: Perft = Perft::default();
+
+        for (notation, depth, expected) in [
+            (fen::START, 4, [197_281, 1576, 0, 0, 0, 469, 0, 0, 8]),
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                3,
+                [97862, 17102, 45, 3162, 0, 993, 0, 0, 1],
+            ),
+            (
+                "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+                4,
+                [43238, 3348, 123, 0, 0, 1680, 106, 0, 17],
+            ),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                3,
+                [9467, 1021, 4, 0, 120, 38, 2, 0, 22],
+            ),
+        ] {
+            let [nodes, captures, en_passant, castles, promotions, checks, discovered_checks, double_checks, checkmates] =
+                expected;
+            let stats: PerftStats = perft.stats(&fen::parse(notation).unwrap(), depth).unwrap();
+
+            assert_eq!(
+                stats,
+                PerftStats {
+                    nodes,
+                    captures,
+                    en_passant,
+                    castles,
+                    promotions,
+                    checks,
+                    discovered_checks,
+                    double_checks,
+                    checkmates,
+                },
+                "{notation}"
+            );
+        }
+    }
+
+    #[test]
+    fn threads_and_hash_agree_with_a_plain_count() {
+        let board: BoardState =
+            fen::parse("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let mut plain: Perft = Perft::default();
+        let mut fast: Perft = Perft {
+            threads: 3,
+            ..Perft::default()
+        };
+        fast.set_hash_size(1);
+
+        assert_eq!(
+            fast.divide(&board, 3).unwrap(),
+            plain.divide(&board, 3).unwrap()
+        );
+        assert_eq!(fast.nodes(&board, 3).unwrap(), 97862);
+        // Repeated from the hash table
+        assert_eq!(fast.nodes(&board, 3).unwrap(), 97862);
     }
 }
@@ -0,0 +1,46 @@
+use crate::types::chess_move::Move;
+
+use super::MAX_PLY;
+
+const SIZE: usize = MAX_PLY as usize + 1;
+
+/// Triangular principal variation table, row `ply` holds the best line found from that ply onwards
+pub struct PvTable {
+    moves: Vec<[Move; SIZE]>,
+    length: [usize; SIZE],
+}
+
+impl Default for PvTable {
+    fn default() -> Self {
+        Self {
+            moves: vec![[Move::NULL; SIZE]; SIZE],
+            length: [0; SIZE],
+        }
+    }
+}
+
+impl PvTable {
+    /// Called on entering a node, before any of its children are searched
+    pub fn clear(&mut self, ply: i32) {
+        let ply: usize = ply as usize;
+        self.length[ply] = ply;
+    }
+
+    /// Makes `mv` followed by the child's line the principal variation at `ply`
+    pub fn update(&mut self, ply: i32, mv: Move) {
+        let ply: usize = ply as usize;
+        let child_length: usize = self.length[ply + 1].max(ply + 1);
+
+        self.moves[ply][ply] = mv;
+
+        for i in ply + 1..child_length {
+            self.moves[ply][i] = self.moves[ply + 1][i];
+        }
+
+        self.length[ply] = child_length;
+    }
+
+    pub fn line(&self) -> Vec<Move> {
+        self.moves[0][..self.length[0]].to_vec()
+    }
+}
@@ -0,0 +1,82 @@
+use std::time::{Duration, Instant};
+
+use crate::types::colour::Colour;
+
+use super::limits::SearchLimits;
+
+/// Time kept back for communication with the GUI
+const MOVE_OVERHEAD: u64 = 30;
+/// Number of moves the remaining time is spread across when `movestogo` isn't given
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+
+/// Decides when a search should stop, timing from its creation.
+///
+/// The soft deadline is checked between iterations, so no new depth is started past it, and the
+/// hard deadline aborts the search wherever it is.
+pub struct TimeManager {
+    start: Instant,
+    soft: Option<Duration>,
+    hard: Option<Duration>,
+}
+
+impl Default for TimeManager {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            soft: None,
+            hard: None,
+        }
+    }
+}
+
+impl TimeManager {
+    pub fn new(limits: &SearchLimits, us: Colour) -> Self {
+        let (time, inc) = match us {
+            Colour::White => (limits.wtime, limits.winc.unwrap_or(0)),
+            Colour::Black => (limits.btime, limits.binc.unwrap_or(0)),
+        };
+
+        let (soft, hard) = if limits.infinite {
+            (None, None)
+        } else if let Some(movetime) = limits.movetime {
+            let movetime: u64 = movetime.saturating_sub(MOVE_OVERHEAD).max(1);
+            (Some(movetime), Some(movetime))
+        } else if let Some(time) = time {
+            let available: u64 = time.saturating_sub(MOVE_OVERHEAD).max(1);
+            let moves_to_go: u64 = limits.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+
+            let hard: u64 = (available * 3 / 4).max(1);
+            let soft: u64 = (available / moves_to_go + inc * 3 / 4).min(hard);
+
+            (Some(soft), Some(hard.min(soft * 4)))
+        } else {
+            (None, None)
+        };
+
+        Self {
+            start: Instant::now(),
+            soft: soft.map(Duration::from_millis),
+            hard: hard.map(Duration::from_millis),
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.start = Instant::now();
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Past this point there is no use starting another iteration
+    pub fn soft_expired(&self) -> bool {
+        self.soft
+            .is_some_and(|soft: Duration| self.elapsed() >= soft)
+    }
+
+    /// Past this point the search must be abandoned
+    pub fn hard_expired(&self) -> bool {
+        self.hard
+            .is_some_and(|hard: Duration| self.elapsed() >= hard)
+    }
+}
@@ -0,0 +1,164 @@
+use std::mem::size_of;
+
+use crate::types::{chess_move::Move, zobrist::ZobristKey};
+
+use super::MATE_BOUND;
+
+pub const DEFAULT_HASH_MB: usize = 16;
+pub const MAX_HASH_MB: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TTEntry {
+    pub key: ZobristKey,
+    pub depth: i32,
+    pub bound: Bound,
+    pub score: i32,
+    pub best_move: Move,
+    generation: u8,
+}
+
+pub struct TranspositionTable {
+    entries: Vec<Option<TTEntry>>,
+    mask: usize,
+    generation: u8,
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_HASH_MB)
+    }
+}
+
+/// The largest power-of-two number of `T` entries that fits in `mb` megabytes, so a key can be
+/// masked to an index
+pub fn table_len<T>(mb: usize) -> usize {
+    let max_entries: usize = mb * 1024 * 1024 / size_of::<T>();
+
+    if max_entries.is_power_of_two() {
+        max_entries
+    } else {
+        max_entries.next_power_of_two() >> 1
+    }
+}
+
+impl TranspositionTable {
+    /// Allocates a table of `mb` megabytes, between 1 and `MAX_HASH_MB`
+    pub fn new(mb: usize) -> Self {
+        let len: usize = table_len::<Option<TTEntry>>(mb.clamp(1, MAX_HASH_MB));
+
+        Self {
+            entries: vec![None; len],
+            mask: len - 1,
+            generation: 0,
+        }
+    }
+
+    pub fn resize(&mut self, mb: usize) {
+        *self = Self::new(mb);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+        self.generation = 0;
+    }
+
+    /// Marks the start of a new search, so entries left over from earlier searches are replaced first
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// Permille of a sample of the table filled during the current search
+    pub fn hashfull(&self) -> usize {
+        self.entries
+            .iter()
+            .take(1000)
+            .filter(|entry| entry.is_some_and(|entry: TTEntry| entry.generation == self.generation))
+            .count()
+            * 1000
+            / self.entries.len().min(1000)
+    }
+
+    pub fn probe(&self, key: ZobristKey) -> Option<TTEntry> {
+        self.entries[key as usize & self.mask].filter(|entry: &TTEntry| entry.key == key)
+    }
+
+    /// Entries are replaced if they are stale, for the same position, or searched less deeply
+    pub fn store(
+        &mut self,
+        key: ZobristKey,
+        depth: i32,
+        bound: Bound,
+        score: i32,
+        mut best_move: Move,
+        ply: i32,
+    ) {
+        let generation: u8 = self.generation;
+        let slot: &mut Option<TTEntry> = &mut self.entries[key as usize & self.mask];
+
+        if let Some(existing) = slot {
+            let replace: bool =
+                existing.key == key || existing.generation != generation || depth >= existing.depth;
+
+            if !replace {
+                return;
+            }
+
+            // Don't lose the best move of the position when re-storing without one
+            if existing.key == key && best_move == Move::NULL {
+                best_move = existing.best_move;
+            }
+        }
+
+        *slot = Some(TTEntry {
+            key,
+            depth,
+            bound,
+            score: score_to_tt(score, ply),
+            best_move,
+            generation,
+        });
+    }
+}
+
+/// Mate scores are stored relative to the node, converting them from distance-to-root into
+/// distance-to-node
+fn score_to_tt(score: i32, ply: i32) -> i32 {
+    if score >= MATE_BOUND {
+        score + ply
+    } else if score <= -MATE_BOUND {
+        score - ply
+    } else {
+        score
+    }
+}
+
+/// Converts a stored mate score back into distance-to-root at the probing node
+pub fn score_from_tt(score: i32, ply: i32) -> i32 {
+    if score >= MATE_BOUND {
+        score - ply
+    } else if score <= -MATE_BOUND {
+        score + ply
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::table_len;
+
+    #[test]
+    fn tables_round_down_to_a_power_of_two() {
+        assert_eq!(table_len::<[u64; 2]>(1), 1 << 16);
+        // 43690 entries of 24 bytes would fit in a megabyte
+        assert_eq!(table_len::<[u64; 3]>(1), 1 << 15);
+        assert_eq!(table_len::<[u64; 3]>(3), 1 << 17);
+    }
+}
@@ -23,9 +23,9 @@ pub struct BBIterator {
 }
 
 impl Iterator for BBIterator {
-    type Item = (SquareIndex, BB);
+    type Item = SquareIndex;
 
-    fn next(&mut self) -> Option<(SquareIndex, BB)> {
+    fn next(&mut self) -> Option<SquareIndex> {
         if self.bb == 0 {
             return None;
         }
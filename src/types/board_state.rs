@@ -1,13 +1,18 @@
-use crate::fen;
+use crate::{fen, movegen::MoveGen};
 
 use super::{
-    bitboard::{ToBitboard, BB},
-    chess_move::{Move, MoveType::*},
+    bitboard::{PieceItr, ToBitboard, BB},
+    chess_move::{
+        Move,
+        MoveType::{self, *},
+    },
     colour::Colour,
+    game::Status,
     piece_type::PieceType::{self, *},
     position::Position,
     square::{Square::*, SquareIndex},
-    EngineError,
+    zobrist::{self, ZobristKey},
+    EngineError, DARK_SQUARES, LIGHT_SQUARES,
 };
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -18,6 +23,76 @@ pub struct BoardState {
     pub castling_rights: [bool; 4],
     pub half_moves: i32,
     pub full_moves: i32,
+    pub key: ZobristKey,
+    /// Zobrist key of the pawns alone, for the pawn structure hash table
+    pub pawn_key: ZobristKey,
+}
+
+/// Everything `make_move` can't recover from the position after the move, so that
+/// `unmake_move` can take it back
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Undo {
+    pub mv: Move,
+    /// The piece that moved, `None` for a null move
+    pub moved: Option<PieceType>,
+    pub captured: Option<PieceType>,
+    pub castling_rights: [bool; 4],
+    pub en_passant: Option<SquareIndex>,
+    pub half_moves: i32,
+    pub key: ZobristKey,
+    pub pawn_key: ZobristKey,
+}
+
+/// How the search and perft visit the position after a move: copying the board for every child, or
+/// making the move on the board and taking it back afterwards
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum MoveStrategy {
+    #[default]
+    CopyMake,
+    MakeUnmake,
+}
+
+impl TryFrom<&str> for MoveStrategy {
+    type Error = EngineError;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        match name.to_ascii_lowercase().as_str() {
+            "copymake" => Ok(Self::CopyMake),
+            "makeunmake" => Ok(Self::MakeUnmake),
+            _ => Err(EngineError(format!(
+                "[MoveStrategy::try_from()] Unknown move strategy '{name}'"
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for MoveStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CopyMake => write!(f, "CopyMake"),
+            Self::MakeUnmake => write!(f, "MakeUnmake"),
+        }
+    }
+}
+
+impl MoveStrategy {
+    /// Calls `f` with the position after `mv`, leaving `board` as it was
+    pub fn with_move<T>(
+        self,
+        board: &mut BoardState,
+        mv: &Move,
+        f: impl FnOnce(&mut BoardState) -> Result<T, EngineError>,
+    ) -> Result<T, EngineError> {
+        match self {
+            Self::CopyMake => f(&mut board.clone_with_move(mv)?),
+            Self::MakeUnmake => {
+                let undo: Undo = board.make_move(mv)?;
+                let result: Result<T, EngineError> = f(board);
+                board.unmake_move(&undo);
+                result
+            }
+        }
+    }
 }
 
 impl Default for BoardState {
@@ -29,6 +104,8 @@ impl Default for BoardState {
             active_player: Colour::White,
             half_moves: 0,
             full_moves: 0,
+            key: 0,
+            pawn_key: 0,
         }
     }
 }
@@ -55,9 +132,20 @@ impl BoardState {
         Ok(new_pos)
     }
 
-    pub fn make_move(&mut self, mv: &Move) -> Result<(), EngineError> {
+    pub fn make_move(&mut self, mv: &Move) -> Result<Undo, EngineError> {
+        let mut undo: Undo = Undo {
+            mv: *mv,
+            moved: None,
+            captured: None,
+            castling_rights: self.castling_rights,
+            en_passant: self.en_passant,
+            half_moves: self.half_moves,
+            key: self.key,
+            pawn_key: self.pawn_key,
+        };
+
         if mv.kind == Null {
-            return Ok(());
+            return Ok(undo);
         }
 
         let Some(kind) = self.position.type_at(mv.from) else {
@@ -67,15 +155,17 @@ impl BoardState {
         };
         let us: Colour = self.active_player;
 
-        if kind == King {
-            for i in 0..1 {
-                self.castling_rights[i + if self.active_player == Colour::White {
-                    0
-                } else {
-                    2
-                }] = false;
-            }
-        }
+        undo.moved = Some(kind);
+        undo.captured = if mv.kind == EnPassantCapture {
+            Some(Pawn)
+        } else {
+            self.position.type_at(mv.to)
+        };
+
+        self.update_clocks(kind == Pawn || undo.captured.is_some());
+
+        // Castling rights and the en-passant square are hashed out here and back in once they have been updated
+        self.key ^= zobrist::castling(self.castling_rights) ^ zobrist::en_passant(self.en_passant);
 
         if kind == Pawn && ((mv.to as i8) - (mv.from as i8)).abs() == 16 {
             self.en_passant = Some(
@@ -110,11 +200,24 @@ impl BoardState {
                 self.position.remove_piece(us, kind, mv.from);
                 self.position.add_piece(us, kind, mv.to);
             }
+            self.key ^= zobrist::piece(us, kind, mv.from) ^ zobrist::piece(us, kind, mv.to);
+
+            if kind == Pawn {
+                self.pawn_key ^=
+                    zobrist::piece(us, Pawn, mv.from) ^ zobrist::piece(us, Pawn, mv.to);
+            }
         } else if mv.kind == EnPassantCapture {
+            let captured: SquareIndex = (mv.to as i8 - ep_offset) as SquareIndex;
+
             self.position.remove_piece(us, kind, mv.from);
-            self.position
-                .remove_piece(!us, kind, (mv.to as i8 - ep_offset) as SquareIndex);
+            self.position.remove_piece(!us, kind, captured);
             self.position.add_piece(us, kind, mv.to);
+
+            let pawns: ZobristKey = zobrist::piece(us, kind, mv.from)
+                ^ zobrist::piece(!us, kind, captured)
+                ^ zobrist::piece(us, kind, mv.to);
+            self.key ^= pawns;
+            self.pawn_key ^= pawns;
         } else if mv.is_promotion_capture() {
             let capture_kind: PieceType = self.position.type_at(mv.to).unwrap();
 
@@ -129,20 +232,89 @@ impl BoardState {
 
             let add: PieceType = mv.promoted_piece().unwrap();
             self.position.add_piece(us, add, mv.to);
+
+            self.key ^= zobrist::piece(us, kind, mv.from)
+                ^ zobrist::piece(!us, capture_kind, mv.to)
+                ^ zobrist::piece(us, add, mv.to);
+            self.pawn_key ^= zobrist::piece(us, Pawn, mv.from);
         } else if mv.is_promotion() {
             // println!("{:x?} Non-Capture", self.position);
             self.position.remove_piece(us, kind, mv.from);
             let add = mv.promoted_piece().unwrap();
             self.position.add_piece(us, add, mv.to);
+
+            self.key ^= zobrist::piece(us, kind, mv.from) ^ zobrist::piece(us, add, mv.to);
+            self.pawn_key ^= zobrist::piece(us, Pawn, mv.from);
         } else if mv.is_castle() {
             self.position.castle(mv.kind, self.active_player);
+            self.key ^= Self::castle_key(mv.kind, self.active_player);
             self.castling_rights[self.active_player as usize * 2] = false;
             self.castling_rights[self.active_player as usize * 2 + 1] = false;
         }
 
+        self.key ^= zobrist::castling(self.castling_rights) ^ zobrist::en_passant(self.en_passant);
+
         self.switch();
 
-        Ok(())
+        Ok(undo)
+    }
+
+    /// Takes back the move `undo` was returned for, which must be the last move made on the board
+    pub fn unmake_move(&mut self, undo: &Undo) {
+        let mv: &Move = &undo.mv;
+
+        // A null move left the board as it was
+        let Some(moved) = undo.moved else {
+            return;
+        };
+
+        self.active_player = !self.active_player;
+        self.castling_rights = undo.castling_rights;
+        self.en_passant = undo.en_passant;
+        self.half_moves = undo.half_moves;
+        self.key = undo.key;
+        self.pawn_key = undo.pawn_key;
+
+        let us: Colour = self.active_player;
+
+        if us == Colour::Black {
+            self.full_moves -= 1;
+        }
+
+        if mv.is_castle() {
+            self.position.uncastle(mv.kind, us);
+            return;
+        }
+
+        let placed: PieceType = mv.promoted_piece().unwrap_or(moved);
+        self.position.remove_piece(us, placed, mv.to);
+        self.position.add_piece(us, moved, mv.from);
+
+        if let Some(captured) = undo.captured {
+            let square: SquareIndex = if mv.kind == EnPassantCapture {
+                match us {
+                    Colour::White => mv.to - 8,
+                    Colour::Black => mv.to + 8,
+                }
+            } else {
+                mv.to
+            };
+
+            self.position.add_piece(!us, captured, square);
+        }
+    }
+
+    /// The fifty-move clock counts moves since the last pawn move or capture
+    fn update_clocks(&mut self, irreversible: bool) {
+        if irreversible {
+            self.half_moves = 0;
+        } else {
+            self.half_moves += 1;
+        }
+
+        if self.active_player == Colour::Black {
+            self.full_moves += 1;
+        }
     }
 
     fn make_rook_move(&mut self, mv: &Move) {
@@ -168,6 +340,10 @@ impl BoardState {
         if captured == PieceType::Rook {
             self.capture_rook(&mv, active);
         }
+        self.key ^= zobrist::piece(!active, captured, mv.to);
+        if captured == Pawn {
+            self.pawn_key ^= zobrist::piece(!active, Pawn, mv.to);
+        }
         self.position.capture(mv, self.active_player);
     }
 
@@ -203,8 +379,96 @@ impl BoardState {
         }
     }
 
+    fn castle_key(kind: MoveType, colour: Colour) -> ZobristKey {
+        let Some((king_from, king_to, rook_from, rook_to)) = Position::castle_squares(kind, colour)
+        else {
+            return 0;
+        };
+
+        zobrist::piece(colour, King, king_from)
+            ^ zobrist::piece(colour, King, king_to)
+            ^ zobrist::piece(colour, Rook, rook_from)
+            ^ zobrist::piece(colour, Rook, rook_to)
+    }
+
+    /// Whether the game is over in this position. Repetitions need the game's history, see
+    /// `Game::status`
+    pub fn status(&self, gen: &mut MoveGen) -> Result<Status, EngineError> {
+        let status: Status = Status::from_moves(
+            self.active_player,
+            gen.all_moves(self)?.is_empty(),
+            gen.is_check(self, self.active_player),
+        );
+
+        if status != Status::Ongoing {
+            return Ok(status);
+        }
+
+        if self.is_insufficient_material() {
+            Ok(Status::InsufficientMaterial)
+        } else if self.half_moves >= 100 {
+            Ok(Status::FiftyMove)
+        } else {
+            Ok(Status::Ongoing)
+        }
+    }
+
+    /// Neither side can ever mate: bare kings, a single minor piece, or only bishops that all stand
+    /// on the same colour
+    pub fn is_insufficient_material(&self) -> bool {
+        let position: &Position = &self.position;
+
+        if position.bb_piece(Pawn) | position.bb_piece(Rook) | position.bb_piece(Queen) != 0 {
+            return false;
+        }
+
+        let knights: BB = position.bb_piece(Knight);
+        let bishops: BB = position.bb_piece(Bishop);
+
+        if (knights | bishops).count_ones() <= 1 {
+            return true;
+        }
+
+        knights == 0 && (bishops & LIGHT_SQUARES == 0 || bishops & DARK_SQUARES == 0)
+    }
+
+    /// Recalculates the Zobrist key of the position from scratch
+    pub fn compute_key(&self) -> ZobristKey {
+        let mut key: ZobristKey = 0;
+
+        for colour in [Colour::White, Colour::Black] {
+            for piece in [Pawn, Knight, Bishop, Rook, Queen, King] {
+                for square in self.position.bb(colour, piece).iter() {
+                    key ^= zobrist::piece(colour, piece, square);
+                }
+            }
+        }
+
+        key ^= zobrist::castling(self.castling_rights) ^ zobrist::en_passant(self.en_passant);
+
+        if self.active_player == Colour::Black {
+            key ^= zobrist::side();
+        }
+
+        key
+    }
+
+    /// Recalculates the Zobrist key of the pawns from scratch
+    pub fn compute_pawn_key(&self) -> ZobristKey {
+        [Colour::White, Colour::Black]
+            .into_iter()
+            .flat_map(|colour: Colour| {
+                self.position
+                    .bb(colour, Pawn)
+                    .iter()
+                    .map(move |square: SquareIndex| zobrist::piece(colour, Pawn, square))
+            })
+            .fold(0, |key: ZobristKey, pawn: ZobristKey| key ^ pawn)
+    }
+
     fn switch(&mut self) {
         self.active_player = !self.active_player;
+        self.key ^= zobrist::side();
     }
 }
 
@@ -239,3 +503,70 @@ impl std::fmt::Display for BoardState {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        fen,
+        movegen::{
+            test::{walk, WALK_POSITIONS},
+            MoveGen,
+        },
+        types::chess_move::Move,
+    };
+
+    use super::{BoardState, Undo};
+
+    #[test]
+    fn detects_insufficient_material() {
+        for (notation, insufficient) in [
+            ("8/8/4k3/8/8/3K4/8/8 w - - 0 1", true),
+            ("8/8/4k3/8/8/3K4/5B2/8 w - - 0 1", true),
+            ("8/8/4k3/8/8/3K4/5n2/8 w - - 0 1", true),
+            ("8/8/2b1k3/8/8/3K4/5B2/8 w - - 0 1", false),
+            ("8/8/3bk3/8/8/3K4/5B2/8 w - - 0 1", true),
+            ("8/8/4k3/8/8/3K4/5NN1/8 w - - 0 1", false),
+            ("8/8/4k3/8/8/3K4/5BN1/8 w - - 0 1", false),
+            ("8/8/4k3/8/8/3K4/5P2/8 w - - 0 1", false),
+            (fen::START, false),
+        ] {
+            assert_eq!(
+                fen::parse(notation).unwrap().is_insufficient_material(),
+                insufficient,
+                "{notation}"
+            );
+        }
+    }
+
+    #[test]
+    fn null_move_changes_nothing() {
+        // a1 is empty, so there is no piece on the null move's from square
+        let mut board: BoardState = fen::parse("r3k2r/8/8/3pP3/8/8/8/4K2R w Kkq d6 0 1").unwrap();
+        let before: BoardState = board;
+
+        let undo: Undo = board.make_move(&Move::NULL).unwrap();
+        assert_eq!((undo.moved, undo.captured), (None, None));
+        assert_eq!(board, before);
+
+        board.unmake_move(&undo);
+        assert_eq!(board, before);
+    }
+
+    #[test]
+    fn unmake_restores_the_position() {
+        let mut gen: MoveGen = MoveGen::default();
+
+        // Making a move in place gives the same position as making it on a copy, and unmaking
+        // it gives back the original
+        for notation in WALK_POSITIONS {
+            walk(
+                &mut gen,
+                &mut fen::parse(notation).unwrap(),
+                3,
+                &mut |before: &BoardState, mv: &Move, board: &BoardState| {
+                    assert_eq!(*board, before.clone_with_move(mv).unwrap(), "{mv}");
+                },
+            );
+        }
+    }
+}
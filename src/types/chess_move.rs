@@ -1,8 +1,13 @@
-use crate::types::{
-    chess_move::MoveType::*,
-    piece_type::PieceType::{self, *},
-    square::SquareIndex,
-    *,
+use crate::{
+    fen,
+    movegen::MoveGen,
+    types::{
+        board_state::BoardState,
+        chess_move::MoveType::*,
+        piece_type::PieceType::{self, *},
+        square::SquareIndex,
+        *,
+    },
 };
 use std::{fmt::Display, slice::Iter};
 
@@ -22,13 +27,13 @@ impl Display for Move {
         if self.is_promotion() {
             let into = match self.promoted_piece().unwrap() {
                 Rook => "r",
-                Knight => "k",
+                Knight => "n",
                 Bishop => "b",
                 Queen => "q",
                 _ => "?",
             };
 
-            write!(f, "={}", into)?;
+            write!(f, "{into}")?;
         }
 
         Ok(())
@@ -82,16 +87,189 @@ impl Move {
         }
     }
 
-    pub fn to_notation(&self) -> String {
-        let mut notation: String = "".to_string();
+    pub fn is_capture(&self, board: &BoardState) -> bool {
+        self.kind == EnPassantCapture || board.position.type_at(self.to).is_some()
+    }
+
+    /// Standard Algebraic Notation for this move, which must be legal in `board`
+    pub fn to_san(self, board: &BoardState) -> Result<String, EngineError> {
+        let mut gen: MoveGen = MoveGen::default();
+        let mut san: String = String::new();
+
+        match self.kind {
+            CastleKing => san += "O-O",
+            CastleQueen => san += "O-O-O",
+            _ => {
+                let Some(piece) = board.position.type_at(self.from) else {
+                    return Err(EngineError(format!(
+                        "[Move::to_san()] No piece to move for {self}"
+                    )));
+                };
+
+                if piece == Pawn {
+                    if self.is_capture(board) {
+                        san.push(self.from.to_algebraic().remove(0));
+                    }
+                } else {
+                    san.push(piece.to_char());
+                    san += &self.disambiguation(board, &mut gen, piece)?;
+                }
+
+                if self.is_capture(board) {
+                    san.push('x');
+                }
 
-        notation += self.from.to_algebraic().as_str();
-        notation += self.to.to_algebraic().as_str();
+                san += &self.to.to_algebraic();
+
+                if let Some(promoted) = self.promoted_piece() {
+                    san.push('=');
+                    san.push(promoted.to_char());
+                }
+            }
+        }
+
+        let after: BoardState = board.clone_with_move(&self)?;
+
+        if gen.is_check(&after, after.active_player) {
+            san.push(if gen.all_moves(&after)?.is_empty() {
+                '#'
+            } else {
+                '+'
+            });
+        }
 
-        notation
+        Ok(san)
+    }
+
+    /// The file, rank or square of the origin, as much as needed to tell this move apart from
+    /// other moves by the same kind of piece to the same square
+    fn disambiguation(
+        &self,
+        board: &BoardState,
+        gen: &mut MoveGen,
+        piece: PieceType,
+    ) -> Result<String, EngineError> {
+        let from: String = self.from.to_algebraic();
+        let rivals: Vec<SquareIndex> = gen
+            .all_moves(board)?
+            .into_iter()
+            .filter(|mv: &Move| {
+                mv.to == self.to
+                    && mv.from != self.from
+                    && board.position.type_at(mv.from) == Some(piece)
+            })
+            .map(|mv: Move| mv.from)
+            .collect();
+
+        Ok(if rivals.is_empty() {
+            String::new()
+        } else if rivals
+            .iter()
+            .all(|square: &SquareIndex| square % 8 != self.from % 8)
+        {
+            from[..1].to_string()
+        } else if rivals
+            .iter()
+            .all(|square: &SquareIndex| square / 8 != self.from / 8)
+        {
+            from[1..].to_string()
+        } else {
+            from
+        })
+    }
+
+    /// Resolves a move in Standard Algebraic Notation against the legal moves in `board`.
+    /// Check and annotation suffixes are ignored, and `0-0` or a missing `=` are accepted.
+    pub fn from_san(notation: &str, board: &BoardState) -> Result<Move, EngineError> {
+        let san: &str = notation.trim_end_matches(['+', '#', '!', '?']);
+        let moves: Vec<Move> = MoveGen::default().all_moves(board)?;
+
+        let error = |reason: &str| -> EngineError {
+            EngineError(format!("[Move::from_san()] {reason} '{notation}'"))
+        };
+
+        let matches: Vec<Move> = match san {
+            "O-O" | "0-0" => moves
+                .into_iter()
+                .filter(|mv| mv.kind == CastleKing)
+                .collect(),
+            "O-O-O" | "0-0-0" => moves
+                .into_iter()
+                .filter(|mv| mv.kind == CastleQueen)
+                .collect(),
+            _ => {
+                let (san, promotion) = match san.char_indices().last() {
+                    Some((i, c)) if i > 0 && "NBRQ".contains(c) => (
+                        san[..i].trim_end_matches('='),
+                        Some(PieceType::from_char(c)?),
+                    ),
+                    _ => (san, None),
+                };
+
+                let (piece, san) = match san.chars().next() {
+                    Some(c) if "NBRQK".contains(c) => (PieceType::from_char(c)?, &san[1..]),
+                    _ => (Pawn, san),
+                };
+
+                if san.len() < 2 || !san.is_char_boundary(san.len() - 2) {
+                    return Err(error("Malformed move"));
+                }
+
+                let (hint, to) = san.split_at(san.len() - 2);
+                let to: Option<SquareIndex> = square(to);
+                let hint: String = hint.chars().filter(|&c| c != 'x' && c != '-').collect();
+
+                let file: Option<u64> = hint
+                    .chars()
+                    .find_map(|c| "abcdefgh".find(c))
+                    .map(|file| file as u64);
+                let rank: Option<u64> = hint
+                    .chars()
+                    .find_map(|c| c.to_digit(10))
+                    .map(|rank| u64::from(rank) - 1);
+
+                if to.is_none() || !hint.chars().all(|c| matches!(c, 'a'..='h' | '1'..='8')) {
+                    return Err(error("Malformed move"));
+                }
+
+                moves
+                    .into_iter()
+                    .filter(|mv: &Move| {
+                        Some(mv.to) == to
+                            && !mv.is_castle()
+                            && board.position.type_at(mv.from) == Some(piece)
+                            && mv.promoted_piece() == promotion
+                            && file.is_none_or(|file: u64| mv.from % 8 == file)
+                            && rank.is_none_or(|rank: u64| mv.from / 8 == rank)
+                    })
+                    .collect()
+            }
+        };
+
+        match matches[..] {
+            [mv] => Ok(mv),
+            [] => Err(EngineError(format!(
+                "[Move::from_san()] Illegal move '{notation}' in position {}",
+                fen::board_to_fen(board)?
+            ))),
+            _ => Err(error("Ambiguous move")),
+        }
     }
 }
 
+fn square(notation: &str) -> Option<SquareIndex> {
+    let mut chars = notation.chars();
+    let file: u64 = "abcdefgh".find(chars.next()?)? as u64;
+    let rank: u64 = u64::from(
+        chars
+            .next()?
+            .to_digit(10)
+            .filter(|rank| (1..=8).contains(rank))?,
+    );
+
+    Some((rank - 1) * 8 + file)
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MoveType {
     Normal,
@@ -149,3 +327,57 @@ pub enum PromotionType {
     Capture,
     Push,
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{fen, movegen::MoveGen, types::board_state::BoardState, uci::parse_move};
+
+    use super::Move;
+
+    fn san(notation: &str, uci: &str) -> String {
+        let board: BoardState = fen::parse(notation).unwrap();
+        let mv: Move = parse_move(&board, &mut MoveGen::default(), uci).unwrap();
+        let san: String = mv.to_san(&board).unwrap();
+
+        assert_eq!(Move::from_san(&san, &board).unwrap(), mv, "{san}");
+        san
+    }
+
+    #[test]
+    fn writes_and_reads_san() {
+        let kiwipete: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+        assert_eq!(san(fen::START, "e2e4"), "e4");
+        assert_eq!(san(fen::START, "g1f3"), "Nf3");
+        assert_eq!(san(kiwipete, "e1g1"), "O-O");
+        assert_eq!(san(kiwipete, "e1c1"), "O-O-O");
+        assert_eq!(san(kiwipete, "d5e6"), "dxe6");
+        assert_eq!(san(kiwipete, "e2a6"), "Bxa6");
+        assert_eq!(san(kiwipete, "f3f6"), "Qxf6");
+        assert_eq!(san("4k3/8/8/8/8/2N3N1/8/4K3 w - - 0 1", "c3e4"), "Nce4");
+        assert_eq!(san("4k3/8/8/8/8/8/8/R4RK1 w - - 0 1", "a1d1"), "Rad1");
+        assert_eq!(san("4k3/8/8/8/8/8/8/R3K2R w - - 0 1", "a1a8"), "Ra8+");
+        assert_eq!(san("6k1/5ppp/8/8/8/8/8/R3R1K1 w - - 0 1", "e1e8"), "Re8#");
+        assert_eq!(san("3r4/4P3/8/8/8/8/k7/4K3 w - - 0 1", "e7d8q"), "exd8=Q");
+        assert_eq!(san("R7/8/8/8/R7/8/8/R3K2k w - - 0 1", "a4a6"), "R4a6");
+        assert_eq!(san("Q1Q5/8/Q7/8/8/8/8/4K2k w - - 0 1", "a8b7"), "Qa8b7+");
+    }
+
+    #[test]
+    fn reads_loose_san() {
+        let board: BoardState = fen::parse("3r4/4P3/8/8/8/8/k7/4K3 w - - 0 1").unwrap();
+
+        assert_eq!(
+            Move::from_san("exd8Q+", &board).unwrap().to_string(),
+            "e7d8q"
+        );
+        assert_eq!(
+            Move::from_san("e8=N!?", &board).unwrap().to_string(),
+            "e7e8n"
+        );
+        assert!(Move::from_san("e8", &board).is_err());
+        assert!(Move::from_san("Kd8", &board).is_err());
+        assert!(Move::from_san("Zz9", &board).is_err());
+        assert!(Move::from_san("", &board).is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use crate::movegen::MoveGen;
+
+use super::{
+    board_state::BoardState, chess_move::Move, colour::Colour, zobrist::ZobristKey, EngineError,
+};
+
+/// Keys of the positions that came before the current one, oldest first
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct History {
+    keys: Vec<ZobristKey>,
+}
+
+impl History {
+    pub fn push(&mut self, key: ZobristKey) {
+        self.keys.push(key);
+    }
+
+    pub fn pop(&mut self) -> Option<ZobristKey> {
+        self.keys.pop()
+    }
+
+    /// How many times `board` has been seen before. Only positions since the last pawn move or
+    /// capture with the same side to move can be repeats
+    pub fn repetitions(&self, board: &BoardState) -> usize {
+        self.keys
+            .iter()
+            .rev()
+            .take(board.half_moves as usize)
+            .skip(1)
+            .step_by(2)
+            .filter(|&&key| key == board.key)
+            .count()
+    }
+}
+
+/// Whether the game is over, and how
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Ongoing,
+    /// Won by the given colour
+    Checkmate(Colour),
+    Stalemate,
+    FiftyMove,
+    Threefold,
+    InsufficientMaterial,
+}
+
+impl Status {
+    /// Checkmate or stalemate when `to_move` has no legal moves, depending on whether it is in
+    /// check, and otherwise ongoing as far as the moves can tell
+    pub fn from_moves(to_move: Colour, no_moves: bool, in_check: bool) -> Self {
+        match (no_moves, in_check) {
+            (true, true) => Status::Checkmate(!to_move),
+            (true, false) => Status::Stalemate,
+            (false, _) => Status::Ongoing,
+        }
+    }
+
+    pub fn is_draw(self) -> bool {
+        !matches!(self, Status::Ongoing | Status::Checkmate(_))
+    }
+
+    /// The result as written in a PGN game
+    pub fn result(self) -> &'static str {
+        match self {
+            Status::Ongoing => "*",
+            Status::Checkmate(Colour::White) => "1-0",
+            Status::Checkmate(Colour::Black) => "0-1",
+            _ => "1/2-1/2",
+        }
+    }
+}
+
+/// A position together with the moves and positions leading up to it
+#[derive(Clone, Debug, Default)]
+pub struct Game {
+    pub start: BoardState,
+    pub moves: Vec<Move>,
+    pub board: BoardState,
+    pub history: History,
+}
+
+impl Game {
+    pub fn new(board: BoardState) -> Self {
+        Self {
+            start: board,
+            moves: Vec::new(),
+            board,
+            history: History::default(),
+        }
+    }
+
+    pub fn make_move(&mut self, mv: &Move) -> Result<(), EngineError> {
+        let key: ZobristKey = self.board.key;
+        self.board.make_move(mv)?;
+        self.history.push(key);
+        self.moves.push(*mv);
+
+        Ok(())
+    }
+
+    /// As `BoardState::status`, also recognising the third occurrence of a position
+    pub fn status(&self, gen: &mut MoveGen) -> Result<Status, EngineError> {
+        let status: Status = self.board.status(gen)?;
+
+        if status == Status::Ongoing && self.history.repetitions(&self.board) >= 2 {
+            return Ok(Status::Threefold);
+        }
+
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{fen, movegen::MoveGen, types::colour::Colour, uci::parse_move};
+
+    use super::{Game, Status};
+
+    #[test]
+    fn classifies_positions_by_moves_and_check() {
+        assert_eq!(
+            Status::from_moves(Colour::Black, true, true),
+            Status::Checkmate(Colour::White)
+        );
+        assert_eq!(
+            Status::from_moves(Colour::Black, true, false),
+            Status::Stalemate
+        );
+        assert_eq!(
+            Status::from_moves(Colour::White, false, true),
+            Status::Ongoing
+        );
+    }
+
+    #[test]
+    fn counts_repetitions_of_the_side_to_move() {
+        let mut gen: MoveGen = MoveGen::default();
+        let mut game: Game = Game::new(fen::parse(fen::START).unwrap());
+
+        for (i, notation) in [
+            "g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8",
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let mv = parse_move(&game.board, &mut gen, notation).unwrap();
+            game.make_move(&mv).unwrap();
+
+            let expected: usize = match i {
+                3..=6 => 1,
+                7 => 2,
+                _ => 0,
+            };
+            assert_eq!(
+                game.history.repetitions(&game.board),
+                expected,
+                "{notation}"
+            );
+        }
+
+        assert_eq!(game.status(&mut gen).unwrap(), Status::Threefold);
+
+        // A pawn move makes every earlier position unreachable
+        let mv = parse_move(&game.board, &mut gen, "e2e4").unwrap();
+        game.make_move(&mv).unwrap();
+        assert_eq!(game.board.half_moves, 0);
+        assert_eq!(game.history.repetitions(&game.board), 0);
+        assert_eq!(game.status(&mut gen).unwrap(), Status::Ongoing);
+    }
+
+    #[test]
+    fn classifies_finished_games() {
+        let mut gen: MoveGen = MoveGen::default();
+
+        for (notation, status) in [
+            ("6k1/5ppp/8/8/8/8/8/3R2K1 b - - 0 1", Status::Ongoing),
+            (
+                "3R2k1/5ppp/8/8/8/8/8/6K1 b - - 0 1",
+                Status::Checkmate(Colour::White),
+            ),
+            ("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", Status::Stalemate),
+            ("6rk/8/8/8/8/8/5PPP/6K1 w - - 100 80", Status::FiftyMove),
+            ("4k3/8/8/8/8/8/8/3RK2r w - - 100 80", Status::FiftyMove),
+            ("7k/8/8/8/8/8/5PPr/6K1 w - - 0 1", Status::Ongoing),
+            (
+                "r6k/8/8/8/8/8/5PPP/r5K1 w - - 100 80",
+                Status::Checkmate(Colour::Black),
+            ),
+            (
+                "8/8/4k3/8/8/3K4/5N2/8 w - - 0 1",
+                Status::InsufficientMaterial,
+            ),
+        ] {
+            let game: Game = Game::new(fen::parse(notation).unwrap());
+            assert_eq!(game.status(&mut gen).unwrap(), status, "{notation}");
+        }
+    }
+}
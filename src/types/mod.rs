@@ -6,11 +6,12 @@ pub mod bitboard;
 pub mod board_state;
 pub mod chess_move;
 pub mod colour;
+pub mod game;
 pub mod helpers;
-pub mod piece;
 pub mod piece_type;
 pub mod position;
 pub mod square;
+pub mod zobrist;
 
 pub const NORTH: i8 = 8;
 pub const SOUTH: i8 = -8;
@@ -35,6 +36,9 @@ pub const _FILEF: BB = FILEA << 5;
 pub const FILEG: BB = FILEA << 6;
 pub const FILEH: BB = FILEA << 7;
 
+pub const LIGHT_SQUARES: BB = 0x55AA_55AA_55AA_55AA;
+pub const DARK_SQUARES: BB = !LIGHT_SQUARES;
+
 #[derive(Debug)]
 pub struct EngineError(pub String);
 
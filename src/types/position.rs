@@ -70,44 +70,42 @@ impl Position {
     }
 
     pub fn castle(&mut self, kind: MoveType, color: Colour) {
-        match kind {
-            CastleKing => self.castle_king(color),
-            CastleQueen => self.castle_queen(color),
-            _ => {}
+        if let Some((king_from, king_to, rook_from, rook_to)) = Self::castle_squares(kind, color) {
+            self.remove_piece(color, PieceType::King, king_from);
+            self.remove_piece(color, PieceType::Rook, rook_from);
+            self.add_piece(color, PieceType::King, king_to);
+            self.add_piece(color, PieceType::Rook, rook_to);
         }
     }
 
-    fn castle_king(&mut self, color: Colour) {
-        match color {
-            Colour::White => {
-                self.remove_piece(color, PieceType::King, E1 as SquareIndex);
-                self.remove_piece(color, PieceType::Rook, H1 as SquareIndex);
-                self.add_piece(color, PieceType::King, G1 as SquareIndex);
-                self.add_piece(color, PieceType::Rook, F1 as SquareIndex);
-            }
-            Colour::Black => {
-                self.remove_piece(color, PieceType::King, E8 as SquareIndex);
-                self.remove_piece(color, PieceType::Rook, H8 as SquareIndex);
-                self.add_piece(color, PieceType::King, G8 as SquareIndex);
-                self.add_piece(color, PieceType::Rook, F8 as SquareIndex);
-            }
+    /// Puts the king and rook back where they stood before castling
+    pub fn uncastle(&mut self, kind: MoveType, color: Colour) {
+        if let Some((king_from, king_to, rook_from, rook_to)) = Self::castle_squares(kind, color) {
+            self.remove_piece(color, PieceType::King, king_to);
+            self.remove_piece(color, PieceType::Rook, rook_to);
+            self.add_piece(color, PieceType::King, king_from);
+            self.add_piece(color, PieceType::Rook, rook_from);
         }
     }
 
-    fn castle_queen(&mut self, color: Colour) {
-        match color {
-            Colour::White => {
-                self.remove_piece(color, PieceType::King, E1 as SquareIndex);
-                self.remove_piece(color, PieceType::Rook, A1 as SquareIndex);
-                self.add_piece(color, PieceType::King, C1 as SquareIndex);
-                self.add_piece(color, PieceType::Rook, D1 as SquareIndex);
-            }
-            Colour::Black => {
-                self.remove_piece(color, PieceType::King, E8 as SquareIndex);
-                self.remove_piece(color, PieceType::Rook, A8 as SquareIndex);
-                self.add_piece(color, PieceType::King, C8 as SquareIndex);
-                self.add_piece(color, PieceType::Rook, D8 as SquareIndex);
-            }
-        }
+    /// The king's and then the rook's from and to squares when castling
+    pub fn castle_squares(
+        kind: MoveType,
+        color: Colour,
+    ) -> Option<(SquareIndex, SquareIndex, SquareIndex, SquareIndex)> {
+        let squares = match (kind, color) {
+            (CastleKing, Colour::White) => (E1, G1, H1, F1),
+            (CastleQueen, Colour::White) => (E1, C1, A1, D1),
+            (CastleKing, Colour::Black) => (E8, G8, H8, F8),
+            (CastleQueen, Colour::Black) => (E8, C8, A8, D8),
+            _ => return None,
+        };
+
+        Some((
+            squares.0 as SquareIndex,
+            squares.1 as SquareIndex,
+            squares.2 as SquareIndex,
+            squares.3 as SquareIndex,
+        ))
     }
 }
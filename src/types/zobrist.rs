@@ -0,0 +1,119 @@
+use super::{colour::Colour, piece_type::PieceType, square::SquareIndex};
+
+pub type ZobristKey = u64;
+
+const SEED: u64 = 0x5EED_CAFE_F00D_D00D;
+
+const PIECE_KEYS: usize = 2 * 6 * 64;
+const CASTLING_KEYS: usize = 4;
+const EN_PASSANT_KEYS: usize = 8;
+const TABLE_SIZE: usize = PIECE_KEYS + CASTLING_KEYS + EN_PASSANT_KEYS + 1;
+
+const CASTLING_OFFSET: usize = PIECE_KEYS;
+const EN_PASSANT_OFFSET: usize = CASTLING_OFFSET + CASTLING_KEYS;
+const SIDE_OFFSET: usize = EN_PASSANT_OFFSET + EN_PASSANT_KEYS;
+
+/// Pseudo-random keys generated at compile time with xorshift64*, so every
+/// build of the engine hashes positions identically
+static KEYS: [ZobristKey; TABLE_SIZE] = generate_keys();
+
+const fn generate_keys() -> [ZobristKey; TABLE_SIZE] {
+    let mut keys: [ZobristKey; TABLE_SIZE] = [0; TABLE_SIZE];
+    let mut state: u64 = SEED;
+    let mut i: usize = 0;
+
+    while i < TABLE_SIZE {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        keys[i] = state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        i += 1;
+    }
+
+    keys
+}
+
+pub fn piece(colour: Colour, piece: PieceType, square: SquareIndex) -> ZobristKey {
+    KEYS[(colour as usize * 6 + piece as usize) * 64 + square as usize]
+}
+
+/// Combined key for a set of castling rights, indexed as in `BoardState::castling_rights`
+pub fn castling(rights: [bool; 4]) -> ZobristKey {
+    rights
+        .iter()
+        .enumerate()
+        .filter(|(_, &right)| right)
+        .fold(0, |key, (i, _)| key ^ KEYS[CASTLING_OFFSET + i])
+}
+
+/// Only the file of the en-passant square is hashed
+pub fn en_passant(square: Option<SquareIndex>) -> ZobristKey {
+    match square {
+        Some(square) => KEYS[EN_PASSANT_OFFSET + (square % 8) as usize],
+        None => 0,
+    }
+}
+
+/// Toggled whenever black is to move
+pub fn side() -> ZobristKey {
+    KEYS[SIDE_OFFSET]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        fen,
+        movegen::{
+            test::{walk, WALK_POSITIONS},
+            MoveGen,
+        },
+        types::{board_state::BoardState, chess_move::Move},
+    };
+
+    #[test]
+    fn incremental_key_matches_recomputation() {
+        let mut gen: MoveGen = MoveGen::default();
+
+        for notation in WALK_POSITIONS {
+            walk(
+                &mut gen,
+                &mut fen::parse(notation).unwrap(),
+                3,
+                &mut |before: &BoardState, mv: &Move, board: &BoardState| {
+                    assert_eq!(board.key, board.compute_key(), "{mv} from {before}");
+                    assert_eq!(
+                        board.pawn_key,
+                        board.compute_pawn_key(),
+                        "{mv} from {before}"
+                    );
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn transpositions_share_a_key() {
+        let start: BoardState = fen::parse(fen::START).unwrap();
+        let mut board: BoardState = start;
+
+        // Nf3 Nf6 Ng1 Ng8
+        for (from, to) in [(6, 21), (62, 45), (21, 6), (45, 62)] {
+            board
+                .make_move(&Move {
+                    from,
+                    to,
+                    kind: crate::types::chess_move::MoveType::Normal,
+                })
+                .unwrap();
+        }
+
+        assert_eq!(board.key, start.key);
+
+        let double_push: BoardState =
+            fen::parse("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+        let no_en_passant: BoardState =
+            fen::parse("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap();
+
+        assert_ne!(double_push.key, no_en_passant.key);
+    }
+}
@@ -1,167 +1,725 @@
 use crate::types::square::SquareIndexMethods;
 use crate::{
-    fen,
+    fen::{self, Epd},
     movegen::MoveGen,
-    search::{perft::Perft, NegaMax, Searcher},
+    pgn::{self, PgnGame},
+    search::{
+        limits::SearchLimits,
+        perft::{Discrepancy, DivideReference, EngineReference, Perft, PerftStats},
+        tt::{DEFAULT_HASH_MB, MAX_HASH_MB},
+        NegaMax, SearchResult, SearchSignals, Searcher,
+    },
     types::{
-        board_state::BoardState,
-        chess_move::{Move, MoveType},
-        colour::Colour::*,
+        board_state::{BoardState, MoveStrategy},
+        chess_move::Move,
+        game::{Game, Status},
+        piece_type::PieceType,
         square::SquareIndex,
         EngineError,
     },
 };
-use std::io::{stdin, BufRead};
+use std::{
+    fs,
+    io::{stdin, BufRead},
+    ops::ControlFlow,
+    sync::{
+        atomic::Ordering,
+        mpsc::{self, Receiver},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
 
 pub fn uci_loop() -> Result<(), EngineError> {
-    let mut board: BoardState = fen::parse(fen::START)?;
-    let mut searcher: NegaMax = NegaMax::default();
+    let mut game: Game = Game::new(fen::parse(fen::START)?);
+    let mut search: SearchThread = SearchThread::default();
 
-    loop {
-        match uci_execute(&mut board, &mut searcher) {
+    for input in spawn_input() {
+        match uci_execute(&mut game, &mut search, &input) {
             Err(e) => {
                 dbg!(e);
             }
-            Ok(_) => {}
-        };
+            Ok(ControlFlow::Break(())) => break,
+            Ok(ControlFlow::Continue(())) => {}
+        }
     }
+
+    search.stop();
+
+    Ok(())
 }
 
-pub fn uci_execute(board: &mut BoardState, searcher: &mut NegaMax) -> Result<(), EngineError> {
-    let mut buffer: String = String::new();
-    stdin().lock().read_line(&mut buffer).unwrap();
-    let input: &str = buffer.trim_matches(char::is_whitespace);
+/// Reads stdin on its own thread, so commands like `stop` are seen while a search is running
+fn spawn_input() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for line in stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
+
+pub fn uci_execute(
+    game: &mut Game,
+    search: &mut SearchThread,
+    input: &str,
+) -> Result<ControlFlow<()>, EngineError> {
+    let input: &str = input.trim_matches(char::is_whitespace);
 
     let (command, rest) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
 
     match command {
         "uci" => init_uci(),
         "position" => {
-            *board = update_board(rest)?;
+            *game = update_board(rest)?;
         }
 
-        "go" => go(board, searcher, rest)?,
-        "move" => do_move(board, rest)?,
+        "go" => search.start(game.clone(), parse_limits(&game.board, rest)?),
+        "stop" => search.stop(),
+        "ponderhit" => search.ponderhit(),
+        "quit" => return Ok(ControlFlow::Break(())),
+        "move" => do_move(game, rest)?,
+        "pgn" => pgn_command(game, rest)?,
+        "epd" => run_epd(search, rest)?,
         "isready" => println!("readyok"),
-        "ucinewgame" => {}
-        "d" => println!("\n{}", board),
+        "setoption" => set_option(search.searcher(), rest)?,
+        "ucinewgame" => search.searcher().clear(),
+        "d" => {
+            let status: Status = game.status(&mut MoveGen::default())?;
+            println!(
+                "\n{}\n Status: {status:?} ({})",
+                game.board,
+                status.result()
+            );
+        }
+        "eval" => println!("{}", search.searcher().trace(&game.board)),
         "perft" => {
-            let mut perft: Perft = Perft::default();
-            perft.verbose(
-                &board,
-                rest.parse::<i32>().map_err(Into::<EngineError>::into)?,
+            let (depth, strategy) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+
+            if depth == "suite" {
+                perft_suite(strategy)?;
+                return Ok(ControlFlow::Continue(()));
+            }
+
+            if depth == "divide" {
+                perft_divide(&game.board, strategy)?;
+                return Ok(ControlFlow::Continue(()));
+            }
+
+            if depth == "stats" {
+                perft_stats(&game.board, strategy)?;
+                return Ok(ControlFlow::Continue(()));
+            }
+
+            perft_options(strategy)?.verbose(
+                &game.board,
+                depth.parse::<i32>().map_err(Into::<EngineError>::into)?,
             )?;
         }
         _ => println!("Command not understood"),
     }
 
-    Ok(())
+    Ok(ControlFlow::Continue(()))
+}
+
+/// Owns the searcher, lending it to a worker thread for the duration of each `go`
+pub struct SearchThread {
+    searcher: Option<NegaMax>,
+    worker: Option<JoinHandle<NegaMax>>,
+    signals: SearchSignals,
+}
+
+impl Default for SearchThread {
+    fn default() -> Self {
+        let mut searcher: NegaMax = NegaMax::default();
+        let signals: SearchSignals = searcher.signals();
+
+        searcher.set_info(true);
+
+        Self {
+            searcher: Some(searcher),
+            worker: None,
+            signals,
+        }
+    }
+}
+
+impl SearchThread {
+    /// The idle searcher, stopping any search in progress first
+    fn searcher(&mut self) -> &mut NegaMax {
+        self.stop();
+        self.searcher
+            .as_mut()
+            .expect("searcher is returned when the worker is joined")
+    }
+
+    fn start(&mut self, game: Game, limits: SearchLimits) {
+        self.stop();
+
+        let mut searcher: NegaMax = self
+            .searcher
+            .take()
+            .expect("searcher is returned when the worker is joined");
+        let signals: SearchSignals = self.signals.clone();
+
+        signals.stop.store(false, Ordering::Relaxed);
+        signals.ponder.store(limits.ponder, Ordering::Relaxed);
+
+        self.worker = Some(thread::spawn(move || {
+            let result: Result<SearchResult, EngineError> = searcher.search(&game, &limits);
+
+            // The best move may only be sent once the GUI has stopped an infinite or pondering search
+            while (limits.infinite || signals.ponder.load(Ordering::Relaxed))
+                && !signals.stop.load(Ordering::Relaxed)
+            {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            match result {
+                Ok(result) => report(&result),
+                Err(e) => {
+                    dbg!(e);
+                }
+            }
+
+            searcher
+        }));
+    }
+
+    /// Stops any search in progress and waits for it, leaving the signals clear for the next one.
+    /// A search that panicked is answered with a null move and replaced by a new searcher.
+    fn stop(&mut self) {
+        self.signals.stop.store(true, Ordering::Relaxed);
+
+        if let Some(worker) = self.worker.take() {
+            if let Ok(searcher) = worker.join() {
+                self.searcher = Some(searcher);
+            } else {
+                // The panic message has already been printed to stderr
+                println!("info string The search failed, starting again with a new searcher");
+                println!("bestmove 0000");
+                *self = Self::default();
+            }
+        }
+
+        self.signals.stop.store(false, Ordering::Relaxed);
+        self.signals.ponder.store(false, Ordering::Relaxed);
+    }
+
+    fn ponderhit(&mut self) {
+        self.signals.ponder.store(false, Ordering::Relaxed);
+    }
+
+    /// Searches on the calling thread without printing `info` lines, for tools that want the result
+    fn search_quietly(
+        &mut self,
+        game: &Game,
+        limits: &SearchLimits,
+    ) -> Result<SearchResult, EngineError> {
+        let searcher: &mut NegaMax = self.searcher();
+
+        searcher.set_info(false);
+        let result: Result<SearchResult, EngineError> = searcher.search(game, limits);
+        searcher.set_info(true);
+
+        result
+    }
 }
 
+/// Search time per position for `epd` when no other limit is given
+const EPD_MOVETIME: u64 = 1000;
+
+/// Size of the subtree count table for `perft` when no other is given
+const PERFT_HASH_MB: usize = 64;
+
 fn init_uci() {
     println!("id name Rusty");
     println!("id author Fergus Rorke");
+    println!("option name Hash type spin default {DEFAULT_HASH_MB} min 1 max {MAX_HASH_MB}");
+    println!(
+        "option name MoveStrategy type combo default {} var {} var {}",
+        MoveStrategy::default(),
+        MoveStrategy::CopyMake,
+        MoveStrategy::MakeUnmake
+    );
     println!("uciok");
 }
 
-fn update_board<'b>(args: &str) -> Result<BoardState, EngineError> {
-    let (keyword, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+fn set_option(searcher: &mut NegaMax, args: &str) -> Result<(), EngineError> {
+    let Some(args) = args.strip_prefix("name ") else {
+        return Err(EngineError(String::from(
+            "[uci::set_option()] Expected 'name' after 'setoption'",
+        )));
+    };
+    let (name, value) = args.split_once(" value ").unwrap_or((args, ""));
 
-    if keyword == "start" {
-        let mut board: BoardState = fen::parse(fen::START)?;
-        let tokens: Vec<&str> = rest.split(char::is_whitespace).collect();
+    match name.trim() {
+        "Hash" => {
+            let mb: usize = value.trim().parse().map_err(Into::<EngineError>::into)?;
+            searcher.set_hash_size(mb);
+        }
+        "MoveStrategy" => searcher.set_strategy(MoveStrategy::try_from(value.trim())?),
+        name => {
+            return Err(EngineError(format!(
+                "[uci::set_option()] Unknown option '{name}'"
+            )));
+        }
+    }
 
-        if tokens[0] == "move" {
-            let mut gen: MoveGen = MoveGen::default();
-            for notation in tokens[1..].iter() {
-                apply_move(&mut board, *notation, &mut gen)?;
-            }
+    Ok(())
+}
+
+/// Parses `startpos | fen <fen>` followed by an optional `moves <move>...` list
+fn update_board(args: &str) -> Result<Game, EngineError> {
+    let (setup, moves) = match args.split_once("moves") {
+        Some((setup, moves)) => (setup.trim(), Some(moves)),
+        None => (args.trim(), None),
+    };
+
+    let (keyword, fen) = setup.split_once(char::is_whitespace).unwrap_or((setup, ""));
+
+    let board: BoardState = match keyword {
+        "startpos" if fen.is_empty() => fen::parse(fen::START)?,
+        "startpos" => {
+            return Err(EngineError(format!(
+                "[uci::update_board()] Unexpected '{fen}' after 'startpos'"
+            )))
+        }
+        "fen" => fen::parse(fen)?,
+        "" => {
+            return Err(EngineError(String::from(
+                "[uci::update_board()] Expected 'startpos' or 'fen'",
+            )))
+        }
+        keyword => {
+            return Err(EngineError(format!(
+                "[uci::update_board()] Unknown position keyword '{keyword}'"
+            )))
         }
+    };
 
-        Ok(board)
-    } else {
-        fen::parse(rest)
+    let mut game: Game = Game::new(board);
+
+    if let Some(moves) = moves {
+        let mut gen: MoveGen = MoveGen::default();
+
+        for notation in moves.split_whitespace() {
+            let mv: Move = parse_move(&game.board, &mut gen, notation)?;
+            game.make_move(&mv)?;
+        }
     }
+
+    Ok(game)
 }
 
-fn go<S>(board: &mut BoardState, searcher: &mut S, rest: &str) -> Result<(), EngineError>
-where
-    S: Searcher,
-{
-    // let movetime = data[2].parse::<u128>().unwrap();
-    // searcher.move_time((movetime / 1000) - 1);
-    // searcher.move_time(movetime);
+/// Resolves a move in UCI long algebraic notation, e.g. `e2e4` or `e7e8q`, against the legal moves
+pub fn parse_move(
+    board: &BoardState,
+    gen: &mut MoveGen,
+    notation: &str,
+) -> Result<Move, EngineError> {
+    let chars: Vec<char> = notation.chars().collect();
+
+    let is_square =
+        |file: char, rank: char| ('a'..='h').contains(&file) && ('1'..='8').contains(&rank);
+
+    if !(chars.len() == 4 || chars.len() == 5)
+        || !is_square(chars[0], chars[1])
+        || !is_square(chars[2], chars[3])
+    {
+        return Err(EngineError(format!(
+            "[uci::parse_move()] Malformed move '{notation}'"
+        )));
+    }
 
-    let depth = rest.parse::<i32>().map_err(Into::<EngineError>::into)?;
+    let from: SquareIndex = SquareIndex::parse(&notation[0..2]);
+    let to: SquareIndex = SquareIndex::parse(&notation[2..4]);
+    let promotion: Option<PieceType> = match chars.get(4) {
+        Some('n') => Some(PieceType::Knight),
+        Some('b') => Some(PieceType::Bishop),
+        Some('r') => Some(PieceType::Rook),
+        Some('q') => Some(PieceType::Queen),
+        Some(c) => {
+            return Err(EngineError(format!(
+                "[uci::parse_move()] Unknown promotion piece '{c}' in '{notation}'"
+            )))
+        }
+        None => None,
+    };
+
+    gen.all_moves(board)?
+        .into_iter()
+        .find(|mv: &Move| mv.from == from && mv.to == to && mv.promoted_piece() == promotion)
+        .ok_or(EngineError(format!(
+            "[uci::parse_move()] Illegal move '{notation}' in position {}",
+            fen::board_to_fen(board)?
+        )))
+}
 
-    let (mv, eval) = searcher.search(*board, depth)?;
+fn report(result: &SearchResult) {
+    match (result.best_move, result.pv.get(1)) {
+        (Some(mv), Some(ponder)) => println!("bestmove {mv} ponder {ponder}"),
+        (Some(mv), None) => println!("bestmove {mv}"),
+        (None, _) => println!("bestmove 0000"),
+    }
+}
 
-    println!("eval {}", eval);
+fn parse_limits(board: &BoardState, args: &str) -> Result<SearchLimits, EngineError> {
+    let mut limits: SearchLimits = SearchLimits::default();
+    let mut tokens = args.split_whitespace().peekable();
 
-    if let Some(mv) = mv {
-        println!("bestmove {}", mv);
+    while let Some(token) = tokens.next() {
+        let mut value = || -> Result<&str, EngineError> {
+            tokens.next().ok_or(EngineError(format!(
+                "[uci::parse_limits()] Missing value for '{token}'"
+            )))
+        };
+
+        match token {
+            "wtime" => limits.wtime = Some(value()?.parse()?),
+            "btime" => limits.btime = Some(value()?.parse()?),
+            "winc" => limits.winc = Some(value()?.parse()?),
+            "binc" => limits.binc = Some(value()?.parse()?),
+            "movestogo" => limits.movestogo = Some(value()?.parse()?),
+            "movetime" => limits.movetime = Some(value()?.parse()?),
+            "depth" => limits.depth = Some(value()?.parse()?),
+            "nodes" => limits.nodes = Some(value()?.parse()?),
+            "mate" => limits.mate = Some(value()?.parse()?),
+            "infinite" => limits.infinite = true,
+            "ponder" => limits.ponder = true,
+            "searchmoves" => {
+                let mut gen: MoveGen = MoveGen::default();
+
+                while let Some(notation) = tokens.next_if(|token: &&str| !is_go_keyword(token)) {
+                    limits
+                        .searchmoves
+                        .push(parse_move(board, &mut gen, notation)?);
+                }
+            }
+            token => {
+                return Err(EngineError(format!(
+                    "[uci::parse_limits()] Unknown go parameter '{token}'"
+                )))
+            }
+        }
     }
 
+    Ok(limits)
+}
+
+fn is_go_keyword(token: &str) -> bool {
+    [
+        "wtime",
+        "btime",
+        "winc",
+        "binc",
+        "movestogo",
+        "movetime",
+        "depth",
+        "nodes",
+        "mate",
+        "infinite",
+        "searchmoves",
+        "ponder",
+    ]
+    .contains(&token)
+}
+
+/// Plays a move given in SAN, e.g. `Nf3` or `O-O`, or in UCI long algebraic notation
+fn do_move(game: &mut Game, rest: &str) -> Result<(), EngineError> {
+    let notation: &str = rest.trim();
+    let mv: Move = Move::from_san(notation, &game.board).or_else(|san_error: EngineError| {
+        parse_move(&game.board, &mut MoveGen::default(), notation).map_err(|_| san_error)
+    })?;
+
+    println!("{} ({mv})", mv.to_san(&game.board)?);
+    game.make_move(&mv)?;
+
     Ok(())
 }
 
-fn do_move(board: &mut BoardState, rest: &str) -> Result<(), EngineError> {
-    let mv: Move = match rest {
-        "O-O" => {
-            let (from, to) = match board.active_player {
-                White => (4, 6),
-                Black => (60, 62),
-            };
+/// `epd <file> [go parameters]` searches every position of a test suite that has a `bm` or `am`
+/// operation, one second each unless limited otherwise, and reports how many were solved
+fn run_epd(search: &mut SearchThread, args: &str) -> Result<(), EngineError> {
+    let (path, go) = args
+        .trim()
+        .split_once(char::is_whitespace)
+        .unwrap_or((args.trim(), ""));
 
-            Move {
-                from,
-                to,
-                kind: MoveType::CastleKing,
-            }
+    let text: String = fs::read_to_string(path)
+        .map_err(|e| EngineError(format!("[uci::run_epd()] Can't read '{path}': {e}")))?;
+
+    let mut solved: usize = 0;
+    let mut total: usize = 0;
+
+    for (number, record) in text.lines().enumerate() {
+        if record.trim().is_empty() || record.starts_with('#') {
+            continue;
         }
-        "O-O-O" => {
-            let (from, to) = match board.active_player {
-                White => (4, 2),
-                Black => (60, 58),
-            };
 
-            Move {
-                from,
-                to,
-                kind: MoveType::CastleQueen,
-            }
+        let epd: Epd = fen::parse_epd(record).map_err(|e: EngineError| {
+            EngineError(format!("[uci::run_epd()] Line {}: {}", number + 1, e.0))
+        })?;
+        let best: Vec<Move> = epd.best_moves()?;
+        let avoid: Vec<Move> = epd.avoid_moves()?;
+
+        if best.is_empty() && avoid.is_empty() {
+            continue;
         }
-        _ => {
-            let from: SquareIndex = SquareIndex::parse(&rest[0..2]);
-            let to: SquareIndex = SquareIndex::parse(&rest[2..4]);
-            let kind: MoveType = match &rest[4..] {
-                "=n" => MoveType::KnightPromotion,
-                "=b" => MoveType::BishopPromotion,
-                "=r" => MoveType::RookPromotion,
-                "=q" => MoveType::QueenPromotion,
-                "" => MoveType::Normal,
-                _ => todo!(),
-            };
 
-            Move { from, to, kind }
+        let limits: SearchLimits = epd_limits(&epd.board, go)?;
+        search.searcher().clear();
+        let result: SearchResult = search.search_quietly(&Game::new(epd.board), &limits)?;
+
+        let passed: bool = result.best_move.is_some_and(|mv: Move| {
+            (best.is_empty() || best.contains(&mv)) && !avoid.contains(&mv)
+        });
+        let played: String = match result.best_move {
+            Some(mv) => mv.to_san(&epd.board)?,
+            None => String::from("none"),
+        };
+
+        total += 1;
+        if passed {
+            solved += 1;
         }
+
+        println!(
+            "{} {}: played {played}, {} {}",
+            if passed { "ok  " } else { "FAIL" },
+            epd.id().unwrap_or(&format!("line {}", number + 1)),
+            if best.is_empty() { "avoid" } else { "best" },
+            epd.operation(if best.is_empty() { "am" } else { "bm" })
+                .unwrap_or_default()
+                .join(" ")
+        );
+    }
+
+    println!("Solved {solved}/{total} ({}%)", solved * 100 / total.max(1));
+
+    Ok(())
+}
+
+/// The limits for searching an EPD position, read against its board so `searchmoves` and the side
+/// to move's clock refer to it
+fn epd_limits(board: &BoardState, go: &str) -> Result<SearchLimits, EngineError> {
+    let mut limits: SearchLimits = parse_limits(board, go)?;
+
+    if limits.depth.is_none() && limits.movetime.is_none() && limits.nodes.is_none() {
+        limits.movetime = Some(EPD_MOVETIME);
+    }
+
+    Ok(limits)
+}
+
+/// Reads the `[strategy] [threads <n>] [hash <MB>]` options of the `perft` commands. By default
+/// every core is used, with a table of `PERFT_HASH_MB`.
+fn perft_options(args: &str) -> Result<Perft, EngineError> {
+    let mut perft: Perft = Perft::default();
+    let mut hash_mb: usize = PERFT_HASH_MB;
+    perft.threads = thread::available_parallelism().map_or(1, usize::from);
+
+    let mut tokens = args.split_whitespace();
+
+    while let Some(token) = tokens.next() {
+        let mut value = || -> Result<usize, EngineError> {
+            tokens
+                .next()
+                .ok_or_else(|| {
+                    EngineError(format!(
+                        "[uci::perft_options()] Missing value for '{token}'"
+                    ))
+                })?
+                .parse()
+                .map_err(Into::into)
+        };
+
+        match token {
+            "threads" => perft.threads = value()?,
+            "hash" => hash_mb = value()?,
+            strategy => perft.strategy = MoveStrategy::try_from(strategy)?,
+        }
+    }
+
+    perft.set_hash_size(hash_mb);
+
+    Ok(perft)
+}
+
+/// `perft suite <file> [max depth] [options]` checks the node counts of every position in a perft
+/// EPD file against its `D1` to `D6` operations and reports any mismatch
+fn perft_suite(args: &str) -> Result<(), EngineError> {
+    let (path, rest) = args
+        .trim()
+        .split_once(char::is_whitespace)
+        .unwrap_or((args.trim(), ""));
+    let (max_depth, options) = rest
+        .trim()
+        .split_once(char::is_whitespace)
+        .unwrap_or((rest.trim(), ""));
+    let (max_depth, options): (i32, &str) = match max_depth.parse::<i32>() {
+        Ok(depth) => (depth, options),
+        Err(_) => (i32::MAX, rest),
     };
 
-    board.make_move(&mv)?;
+    let text: String = fs::read_to_string(path)
+        .map_err(|e| EngineError(format!("[uci::perft_suite()] Can't read '{path}': {e}")))?;
+
+    let start: Instant = Instant::now();
+    let mut perft: Perft = perft_options(options)?;
+    let mut nodes_searched: usize = 0;
+    let mut mismatches: usize = 0;
+    let mut checked: usize = 0;
+
+    for (number, record) in text.lines().enumerate() {
+        if record.trim().is_empty() || record.starts_with('#') {
+            continue;
+        }
+
+        let epd: Epd = fen::parse_epd(record).map_err(|e: EngineError| {
+            EngineError(format!("[uci::perft_suite()] Line {}: {}", number + 1, e.0))
+        })?;
+
+        for (depth, expected) in epd.perft_counts()? {
+            if depth > max_depth {
+                break;
+            }
+
+            let nodes: usize = perft.nodes(&epd.board, depth)?;
+            nodes_searched += nodes;
+            checked += 1;
+
+            if nodes != expected {
+                mismatches += 1;
+                println!(
+                    "MISMATCH line {} depth {depth}: expected {expected}, found {nodes}\n{}",
+                    number + 1,
+                    fen::board_to_fen(&epd.board)?
+                );
+            }
+        }
+    }
+
+    let millis: u128 = start.elapsed().as_millis();
+    let nps: u128 = nodes_searched as u128 * 1000 / millis.max(1);
+    println!(
+        "Perft suite: {checked} counts checked, {mismatches} mismatches ({millis}ms, {nps} nps)"
+    );
 
     Ok(())
 }
 
-fn apply_move(
-    board: &mut BoardState,
-    notation: &str,
-    gen: &mut MoveGen,
-) -> Result<(), EngineError> {
-    let move_list: Vec<Move> = gen.all_moves(board)?;
-    let mv: Option<&Move> = move_list
-        .iter()
-        .find(|x: &&Move| x.to_notation() == notation);
-    board.make_move(mv.unwrap())?;
+/// `perft divide <depth> <reference file>` compares our divide of the current position with one
+/// from another engine and follows the first differing count down to the position at fault.
+/// `perft divide <depth> engine <path>` runs that engine instead, asking it for every divide needed.
+fn perft_divide(board: &BoardState, args: &str) -> Result<(), EngineError> {
+    let (depth, path) = args.trim().split_once(char::is_whitespace).ok_or_else(|| {
+        EngineError(String::from(
+            "[uci::perft_divide()] Expected a depth and a reference file or engine",
+        ))
+    })?;
+    let depth: i32 = depth.parse()?;
+    let path: &str = path.trim();
+
+    let mut perft: Perft = Perft::default();
+    perft.verbose(board, depth)?;
+
+    let discrepancy: Option<Discrepancy> = if let Some(engine) = path.strip_prefix("engine ") {
+        perft.bisect(board, depth, &mut EngineReference::spawn(engine.trim())?)?
+    } else {
+        let text: String = fs::read_to_string(path)
+            .map_err(|e| EngineError(format!("[uci::perft_divide()] Can't read '{path}': {e}")))?;
+
+        perft.bisect(board, depth, &mut DivideReference::parse(&text, board)?)?
+    };
+
+    match discrepancy {
+        Some(discrepancy) => println!("\n{discrepancy}"),
+        None => println!("\nAll counts match the reference"),
+    }
+
+    Ok(())
+}
+
+/// `perft stats <depth> [strategy]` prints a table of what the leaf nodes at each depth up to
+/// `depth` were reached by. The stats are counted on one thread without a hash table, so the
+/// `threads` and `hash` options of the other `perft` commands are refused.
+fn perft_stats(board: &BoardState, args: &str) -> Result<(), EngineError> {
+    let (depth, options) = args
+        .trim()
+        .split_once(char::is_whitespace)
+        .unwrap_or((args.trim(), ""));
+    let mut perft: Perft = Perft::default();
+
+    for option in options.split_whitespace() {
+        match option {
+            "threads" | "hash" => {
+                return Err(EngineError(format!(
+                    "[uci::perft_stats()] '{option}' is not supported, stats are counted on one thread without hashing"
+                )))
+            }
+            strategy => perft.strategy = MoveStrategy::try_from(strategy)?,
+        }
+    }
+
+    println!("{}", PerftStats::HEADER);
+
+    for depth in 1..=depth.parse::<i32>()? {
+        println!("{depth:>5} {}", perft.stats(board, depth)?);
+    }
+
+    Ok(())
+}
+
+/// `pgn load <file> [game number]` replaces the game with one read from a PGN file, and `pgn show`
+/// prints the current game as PGN
+fn pgn_command(game: &mut Game, args: &str) -> Result<(), EngineError> {
+    let mut args = args.split_whitespace();
+
+    match args.next() {
+        Some("load") => {
+            let Some(path) = args.next() else {
+                return Err(EngineError(String::from(
+                    "[uci::pgn_command()] Expected a file after 'pgn load'",
+                )));
+            };
+            let number: usize = args.next().map_or(Ok(1), str::parse)?;
+
+            let text: String = fs::read_to_string(path).map_err(|e| {
+                EngineError(format!("[uci::pgn_command()] Can't read '{path}': {e}"))
+            })?;
+            let games: Vec<PgnGame> = pgn::parse(&text)?;
+
+            let Some(loaded) = number.checked_sub(1).and_then(|i| games.get(i)) else {
+                return Err(EngineError(format!(
+                    "[uci::pgn_command()] No game {number} in '{path}', it has {}",
+                    games.len()
+                )));
+            };
+
+            *game = loaded.to_game()?;
+        }
+        Some("show") | None => {
+            print!(
+                "{}",
+                pgn::write(&PgnGame::from_game(game, &mut MoveGen::default())?)?
+            );
+        }
+        Some(command) => {
+            return Err(EngineError(format!(
+                "[uci::pgn_command()] Unknown pgn command '{command}'"
+            )));
+        }
+    }
 
     Ok(())
 }
@@ -174,7 +732,6 @@ fn apply_move(
 //     let args: Vec<&str> = iter.collect::<Vec<&str>>();
 
 //     match cmd {
-//         "eval" => self.command_eval(args),
 //         "search" => self.command_search(args),
 
 //         "magictest" => magics::test(),
@@ -207,3 +764,128 @@ fn apply_move(
 // fn command_perft(&mut self, args: Vec<&str>) {
 //
 // }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        fen,
+        movegen::MoveGen,
+        search::{limits::SearchLimits, NegaMax, SearchResult, Searcher},
+        types::{board_state::BoardState, chess_move::MoveType, game::Game, piece_type::PieceType},
+    };
+
+    use std::thread;
+
+    use super::{epd_limits, parse_move, perft_stats, update_board, SearchThread, EPD_MOVETIME};
+
+    #[test]
+    fn startpos_with_moves() {
+        let game: Game = update_board("startpos moves e2e4 e7e5 g1f3").unwrap();
+        assert_eq!(
+            fen::board_to_fen(&game.board).unwrap(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+        assert_eq!(game.history.repetitions(&game.board), 0);
+    }
+
+    #[test]
+    fn fen_with_moves() {
+        let game: Game =
+            update_board("fen 4k3/8/8/8/8/8/8/R3K3 w Q - 0 1 moves e1c1 e8e7").unwrap();
+        assert_eq!(
+            fen::board_to_fen(&game.board).unwrap(),
+            "8/4k3/8/8/8/8/8/2KR4 w - - 2 2"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_positions() {
+        assert!(update_board("").is_err());
+        assert!(update_board("start").is_err());
+        assert!(update_board("startpos e2e4").is_err());
+        assert!(update_board("startpos moves e2e5").is_err());
+        assert!(update_board("fen 8/8/8 w - - 0 1").is_err());
+    }
+
+    #[test]
+    fn stop_leaves_the_searcher_ready() {
+        let game: Game = Game::new(fen::parse(fen::START).unwrap());
+        let mut search: SearchThread = SearchThread::default();
+
+        search.start(
+            game.clone(),
+            SearchLimits {
+                infinite: true,
+                ..SearchLimits::default()
+            },
+        );
+        search.stop();
+
+        // The next search isn't cut short by the flags the last one was stopped with
+        let searcher: &mut NegaMax = search.searcher();
+        let limits: SearchLimits = SearchLimits {
+            depth: Some(3),
+            ..SearchLimits::default()
+        };
+
+        searcher.set_info(false);
+        let result: SearchResult = searcher.search(&game, &limits).unwrap();
+        assert_eq!(result.depth, 3);
+    }
+
+    #[test]
+    fn stop_survives_a_panicked_search() {
+        let mut search: SearchThread = SearchThread {
+            searcher: None,
+            worker: Some(thread::spawn(|| panic!("search failed"))),
+            ..SearchThread::default()
+        };
+
+        search.stop();
+
+        let game: Game = Game::new(fen::parse(fen::START).unwrap());
+        let limits: SearchLimits = SearchLimits {
+            depth: Some(2),
+            ..SearchLimits::default()
+        };
+        assert_eq!(search.search_quietly(&game, &limits).unwrap().depth, 2);
+    }
+
+    #[test]
+    fn epd_limits_follow_the_position() {
+        // Ra8 is only legal in the EPD position, not the start position
+        let board: BoardState = fen::parse("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap();
+        let limits: SearchLimits = epd_limits(&board, "searchmoves a1a8").unwrap();
+        assert_eq!(limits.searchmoves[0].to_string(), "a1a8");
+        assert_eq!(limits.movetime, Some(EPD_MOVETIME));
+
+        let limits: SearchLimits = epd_limits(&board, "depth 4").unwrap();
+        assert_eq!((limits.depth, limits.movetime), (Some(4), None));
+    }
+
+    #[test]
+    fn perft_stats_refuses_threads_and_hash() {
+        let board: BoardState = fen::parse(fen::START).unwrap();
+
+        assert!(perft_stats(&board, "1 makeunmake").is_ok());
+        assert!(perft_stats(&board, "1 threads 4").is_err());
+        assert!(perft_stats(&board, "1 copymake hash 16").is_err());
+    }
+
+    #[test]
+    fn parses_promotions() {
+        let mut gen: MoveGen = MoveGen::default();
+        let board: BoardState = fen::parse("3r4/4P3/8/8/8/8/k7/4K3 w - - 0 1").unwrap();
+
+        let mv = parse_move(&board, &mut gen, "e7e8n").unwrap();
+        assert_eq!(mv.kind, MoveType::KnightPromotion);
+
+        let mv = parse_move(&board, &mut gen, "e7d8q").unwrap();
+        assert_eq!(mv.promoted_piece(), Some(PieceType::Queen));
+        assert!(mv.is_promotion_capture());
+
+        assert!(parse_move(&board, &mut gen, "e7e8").is_err());
+        assert!(parse_move(&board, &mut gen, "e7e8k").is_err());
+        assert!(parse_move(&board, &mut gen, "e7").is_err());
+    }
+}
@@ -0,0 +1,31 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+#[test]
+fn perft_suite_has_no_mismatches() {
+    let suite: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/perft.epd");
+
+    let mut engine = Command::new(env!("CARGO_BIN_EXE_rusty"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    engine
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(format!("perft suite {suite}\nquit\n").as_bytes())
+        .unwrap();
+
+    let output: Output = engine.wait_with_output().unwrap();
+    let stdout: String = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success(), "{stdout}");
+    assert!(
+        stdout.contains("101 counts checked, 0 mismatches"),
+        "{stdout}"
+    );
+}